@@ -1,15 +1,42 @@
 use std::{
     collections::{HashSet, VecDeque},
     io::{stdin, BufRead},
+    time::Instant,
 };
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--bench") {
+        bench();
+        return;
+    }
     let input = parse_input(stdin().lock());
-    println!("part 1: {}", part_1(&input));
-    println!("part 2: {}", part_2(&input));
+    let arg = |prefix: &str| {
+        std::env::args().find_map(|arg| arg.strip_prefix(prefix).map(str::to_string))
+    };
+    let default = Rules::default();
+    let rules = Rules {
+        start: arg("--start=").map_or(default.start, |start| start.parse().unwrap()),
+        end: arg("--end=").map_or(default.end, |end| end.parse().unwrap()),
+        deltas: arg("--deltas=").map_or(default.deltas, |deltas| {
+            deltas
+                .split(',')
+                .map(|delta| delta.parse().unwrap())
+                .collect()
+        }),
+        diagonal: std::env::args().any(|arg| arg == "--diagonal"),
+    };
+    if let Err(error) = rules.validate() {
+        panic!("invalid rules: {error:?}");
+    }
+    if std::env::args().any(|arg| arg == "--paths") {
+        print_trails(&input, &rules);
+    }
+    println!("part 1: {}", part_1(&input, &rules));
+    println!("part 2: {}", part_2(&input, &rules));
 }
 
-type Input = Vec<Vec<i32>>;
+// Tiles are heights, or `None` for impassable tiles (`.`).
+type Input = Vec<Vec<Option<i32>>>;
 
 fn parse_input(input: impl BufRead) -> Input {
     input
@@ -17,108 +44,451 @@ fn parse_input(input: impl BufRead) -> Input {
         .map(|line| {
             line.unwrap()
                 .chars()
-                .map(|c| c.to_digit(10).unwrap() as i32)
+                .map(|c| match c {
+                    '.' => None,
+                    c => Some(c.to_digit(10).expect("tile should be a digit or '.'") as i32),
+                })
                 .collect()
         })
         .collect()
 }
 
-fn part_1(input: &Input) -> usize {
-    let mut total = 0;
-    for row in 0..input.len() {
-        for col in 0..input[0].len() {
-            if input[row][col] == 0 {
-                total += trail_score(input, (row as isize, col as isize));
-            }
+// Which heights trails start and end at, how much each step may change the
+// height by, and whether steps may be diagonal. Every step has to bring a
+// trail closer to its end, so no trail can go round in circles.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Rules {
+    start: i32,
+    end: i32,
+    deltas: Vec<i32>,
+    diagonal: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            start: 0,
+            end: 9,
+            deltas: vec![1],
+            diagonal: false,
         }
     }
-    total
 }
 
-fn part_2(input: &Input) -> usize {
-    let mut total = 0;
-    for row in 0..input.len() {
-        for col in 0..input[0].len() {
-            if input[row][col] == 0 {
-                total += trail_raiting(input, (row as isize, col as isize));
-            }
+#[derive(Debug, PartialEq, Eq)]
+enum InvalidRules {
+    NoDeltas,
+    HeightOutOfRange { height: i32 },
+    StartIsEnd,
+    AwayFromEnd { delta: i32 },
+}
+
+const CARDINAL: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const COMPASS: [(isize, isize); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+impl Rules {
+    fn validate(&self) -> Result<(), InvalidRules> {
+        if self.deltas.is_empty() {
+            return Err(InvalidRules::NoDeltas);
         }
+        if let Some(&height) = [self.start, self.end]
+            .iter()
+            .find(|height| !(0..=9).contains(*height))
+        {
+            return Err(InvalidRules::HeightOutOfRange { height });
+        }
+        if self.start == self.end {
+            return Err(InvalidRules::StartIsEnd);
+        }
+        let direction = (self.end - self.start).signum();
+        match self.deltas.iter().find(|delta| delta.signum() != direction) {
+            Some(&delta) => Err(InvalidRules::AwayFromEnd { delta }),
+            None => Ok(()),
+        }
+    }
+
+    fn directions(&self) -> &'static [(isize, isize)] {
+        if self.diagonal {
+            &COMPASS
+        } else {
+            &CARDINAL
+        }
+    }
+
+    // The heights a trail can pass through before its end, nearest the end
+    // first.
+    fn heights_from_end(&self) -> Vec<i32> {
+        if self.start < self.end {
+            (self.start..self.end).rev().collect()
+        } else {
+            (self.end + 1..=self.start).collect()
+        }
+    }
+
+    // The most steps a trail can take, and so the furthest it can get from
+    // where it started.
+    fn reach(&self) -> usize {
+        let smallest_step = self.deltas.iter().map(|delta| delta.abs()).min().unwrap();
+        ((self.end - self.start).abs() / smallest_step) as usize
     }
-    total
+}
+
+fn part_1(input: &Input, rules: &Rules) -> u64 {
+    sum_over_trailheads(input, rules, &scores(input, rules))
+}
+
+fn part_2(input: &Input, rules: &Rules) -> u64 {
+    sum_over_trailheads(input, rules, &ratings(input, rules))
+}
+
+fn sum_over_trailheads(input: &Input, rules: &Rules, values: &[Vec<u64>]) -> u64 {
+    cells(input)
+        .filter(|&(row, col)| input[row][col] == Some(rules.start))
+        .map(|(row, col)| values[row][col])
+        .sum()
+}
+
+fn cells(input: &Input) -> impl Iterator<Item = (usize, usize)> + '_ {
+    (0..input.len()).flat_map(move |row| (0..input[row].len()).map(move |col| (row, col)))
 }
 
 fn is_in_bound(input: &Input, (row, col): (isize, isize)) -> bool {
     0 <= row && row < input.len() as isize && 0 <= col && col < input[0].len() as isize
 }
 
-fn neighbors((row, col): (isize, isize)) -> impl Iterator<Item = (isize, isize)> {
-    [
-        (row + 1, col),
-        (row, col + 1),
-        (row - 1, col),
-        (row, col - 1),
-    ]
-    .into_iter()
+// The neighbours of a cell that a trail can step to next.
+fn next_steps<'a>(
+    input: &'a Input,
+    rules: &'a Rules,
+    (row, col): (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    let height = input[row][col];
+    rules
+        .directions()
+        .iter()
+        .map(move |(dr, dc)| (row as isize + dr, col as isize + dc))
+        .filter(|neighbor| is_in_bound(input, *neighbor))
+        .map(|(row, col)| (row as usize, col as usize))
+        .filter(move |&(row, col)| match (height, input[row][col]) {
+            (Some(from), Some(to)) => rules.deltas.contains(&(to - from)),
+            _ => false,
+        })
 }
 
-fn trail_score(input: &Input, starting_at: (isize, isize)) -> usize {
-    let trail_ends: HashSet<_> = trails(input, starting_at)
+// The map's cells grouped by height, each group in row-major order.
+fn by_height(input: &Input) -> Vec<Vec<(usize, usize)>> {
+    let mut levels = vec![Vec::new(); 10];
+    for (row, col) in cells(input) {
+        if let Some(height) = input[row][col] {
+            levels[height as usize].push((row, col));
+        }
+    }
+    levels
+}
+
+// The number of distinct trails from each cell to the end height. Works back
+// from the ends, so each cell's count is the sum of those of the cells it can
+// step to.
+fn ratings(input: &Input, rules: &Rules) -> Vec<Vec<u64>> {
+    let mut counts: Vec<Vec<u64>> = input.iter().map(|row| vec![0; row.len()]).collect();
+    let levels = by_height(input);
+    for &(row, col) in &levels[rules.end as usize] {
+        counts[row][col] = 1;
+    }
+    for height in rules.heights_from_end() {
+        for &(row, col) in &levels[height as usize] {
+            counts[row][col] = next_steps(input, rules, (row, col))
+                .map(|(row, col)| counts[row][col])
+                .sum();
+        }
+    }
+    counts
+}
+
+// The number of distinct trail ends reachable from each cell. Ends are taken
+// 64 at a time and each cell gets a bitset of which of those it can reach,
+// again worked out back from the ends. A trail can only take so many steps, so
+// only the cells within that distance of a batch of ends, across and down,
+// need looking at. Ends are batched by square tiles of that size, so each
+// batch stays close together however wide or tall the map is.
+fn scores(input: &Input, rules: &Rules) -> Vec<Vec<u64>> {
+    let mut scores: Vec<Vec<u64>> = input.iter().map(|row| vec![0; row.len()]).collect();
+    let levels = by_height(input);
+    let reach = rules.reach();
+    let width = input.first().map_or(0, Vec::len);
+    let mut reachable = vec![0u64; input.len() * width];
+    let tile = reach.max(1);
+    let mut ends = levels[rules.end as usize].clone();
+    ends.sort_unstable_by_key(|&(row, col)| (row / tile, col / tile, row, col));
+    for batch in ends.chunks(64) {
+        let top = batch.iter().map(|&(row, _)| row).min().unwrap();
+        let bottom = batch.iter().map(|&(row, _)| row).max().unwrap();
+        let left = batch.iter().map(|&(_, col)| col).min().unwrap();
+        let right = batch.iter().map(|&(_, col)| col).max().unwrap();
+        let rows = top.saturating_sub(reach)..=(bottom + reach).min(input.len() - 1);
+        let (left, right) = (left.saturating_sub(reach), right + reach);
+        // The cells of a height within reach of the batch, a row at a time.
+        let nearby = |height: usize| {
+            let level = &levels[height];
+            rows.clone().flat_map(move |row| {
+                let start = level.partition_point(|&cell| cell < (row, left));
+                let end = level.partition_point(|&cell| cell <= (row, right));
+                &level[start..end]
+            })
+        };
+        for (bit, &(row, col)) in batch.iter().enumerate() {
+            reachable[row * width + col] = 1 << bit;
+        }
+        for height in rules.heights_from_end() {
+            for &(row, col) in nearby(height as usize) {
+                reachable[row * width + col] = next_steps(input, rules, (row, col))
+                    .map(|(row, col)| reachable[row * width + col])
+                    .fold(0, |bits, neighbor| bits | neighbor);
+            }
+        }
+        for height in 0..levels.len() {
+            for &(row, col) in nearby(height) {
+                scores[row][col] += reachable[row * width + col].count_ones() as u64;
+                reachable[row * width + col] = 0;
+            }
+        }
+    }
+    scores
+}
+
+fn print_trails(input: &Input, rules: &Rules) {
+    for cell in cells(input) {
+        for trail in trails(input, rules, cell) {
+            let steps: Vec<String> = trail
+                .iter()
+                .map(|(row, col)| format!("({row}, {col})"))
+                .collect();
+            println!("{}", steps.join(" -> "));
+        }
+    }
+}
+
+fn trail_score(input: &Input, rules: &Rules, starting_at: (usize, usize)) -> usize {
+    let trail_ends: HashSet<_> = trails(input, rules, starting_at)
         .into_iter()
         .map(|trail| trail[trail.len() - 1])
         .collect();
     trail_ends.len()
 }
 
-fn trail_raiting(input: &Input, starting_at: (isize, isize)) -> usize {
-    trails(input, starting_at).len()
+fn trail_raiting(input: &Input, rules: &Rules, starting_at: (usize, usize)) -> usize {
+    trails(input, rules, starting_at).len()
 }
 
-fn trails(input: &Input, starting_at: (isize, isize)) -> Vec<Vec<(isize, isize)>> {
-    if !is_in_bound(input, starting_at) {
-        return Vec::new();
-    }
-    if input[starting_at.0 as usize][starting_at.1 as usize] != 0 {
+// Lists every complete trail from a trailhead. Only practical on small maps,
+// as the number of trails can grow exponentially with their length.
+fn trails(input: &Input, rules: &Rules, starting_at: (usize, usize)) -> Vec<Vec<(usize, usize)>> {
+    if input[starting_at.0][starting_at.1] != Some(rules.start) {
         return Vec::new();
     }
     let mut trails = VecDeque::new();
     trails.push_front(vec![starting_at]);
     let mut complete_trails = Vec::new();
     while let Some(current_trail) = trails.pop_back() {
-        let current = current_trail.last().unwrap();
-        let current_value = input[current.0 as usize][current.1 as usize];
-        if current_value == 9 {
+        let current = *current_trail.last().unwrap();
+        if input[current.0][current.1] == Some(rules.end) {
             complete_trails.push(current_trail);
             continue;
         }
-        neighbors(*current)
-            .filter(|neighbor| is_in_bound(input, *neighbor))
-            .filter(|neighbor| {
-                let neighbor_value = input[neighbor.0 as usize][neighbor.1 as usize];
-                current_value + 1 == neighbor_value
-            })
-            .for_each(|neighbor| {
-                let mut next_trail = current_trail.clone();
-                next_trail.push(neighbor);
-                trails.push_back(next_trail)
-            })
+        next_steps(input, rules, current).for_each(|neighbor| {
+            let mut next_trail = current_trail.clone();
+            next_trail.push(neighbor);
+            trails.push_back(next_trail)
+        })
     }
     complete_trails
 }
 
+// A map made of diagonal bands of rising height, broken up by a few random
+// dips and impassable tiles, so that every trailhead has hundreds of trails to
+// a handful of summits.
+fn generate_map(rows: usize, cols: usize) -> Input {
+    let mut seed = 0x2024_0010u64;
+    let mut random = |below: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed % below
+    };
+    (0..rows)
+        .map(|row| {
+            (0..cols)
+                .map(|col| match random(40) {
+                    0 => None,
+                    1 => Some(random(10) as i32),
+                    _ => Some(((row + col) % 10) as i32),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Times the memoised scores and ratings on large maps, square and very wide,
+// and compares them with listing every trail on a smaller one.
+fn bench() {
+    let rules = Rules::default();
+    for (rows, cols) in [(1000, 1000), (1, 300_000)] {
+        let input = generate_map(rows, cols);
+        let start = Instant::now();
+        let (score, rating) = (part_1(&input, &rules), part_2(&input, &rules));
+        println!(
+            "{rows}x{cols} dp: {score} / {rating} in {:?}",
+            start.elapsed()
+        );
+    }
+    let input = generate_map(100, 100);
+    let start = Instant::now();
+    let (score, rating) = (part_1(&input, &rules), part_2(&input, &rules));
+    println!("100x100 dp: {score} / {rating} in {:?}", start.elapsed());
+    let start = Instant::now();
+    let score: usize = cells(&input)
+        .map(|cell| trail_score(&input, &rules, cell))
+        .sum();
+    let rating: usize = cells(&input)
+        .map(|cell| trail_raiting(&input, &rules, cell))
+        .sum();
+    println!(
+        "100x100 listing: {score} / {rating} in {:?}",
+        start.elapsed()
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     const EXAMPLE: &str = include_str!("../example.txt");
+    const EXAMPLE_2: &str = include_str!("../example2.txt");
+    const EXAMPLE_3: &str = include_str!("../example3.txt");
+    const EXAMPLE_4: &str = include_str!("../example4.txt");
+    const EXAMPLE_5: &str = include_str!("../example5.txt");
+    const EXAMPLE_6: &str = include_str!("../example6.txt");
 
     #[test]
     fn test_part_1_example() {
         let input = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_1(&input), 36);
+        assert_eq!(part_1(&input, &Rules::default()), 36);
     }
 
     #[test]
     fn test_part_2_example() {
         let input = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_2(&input), 81);
+        assert_eq!(part_2(&input, &Rules::default()), 81);
+    }
+
+    #[test]
+    fn test_part_1_dotted_examples() {
+        let rules = Rules::default();
+        assert_eq!(part_1(&parse_input(EXAMPLE_2.as_bytes()), &rules), 2);
+        assert_eq!(part_1(&parse_input(EXAMPLE_3.as_bytes()), &rules), 4);
+        assert_eq!(part_1(&parse_input(EXAMPLE_4.as_bytes()), &rules), 3);
+    }
+
+    #[test]
+    fn test_part_2_dotted_examples() {
+        let rules = Rules::default();
+        assert_eq!(part_2(&parse_input(EXAMPLE_5.as_bytes()), &rules), 3);
+        assert_eq!(part_2(&parse_input(EXAMPLE_3.as_bytes()), &rules), 13);
+        assert_eq!(part_2(&parse_input(EXAMPLE_6.as_bytes()), &rules), 227);
+    }
+
+    #[test]
+    fn test_memoised_matches_listing() {
+        let variants = [
+            Rules::default(),
+            Rules {
+                diagonal: true,
+                ..Rules::default()
+            },
+            Rules {
+                start: 9,
+                end: 0,
+                deltas: vec![-1, -2],
+                diagonal: false,
+            },
+            Rules {
+                start: 2,
+                end: 8,
+                deltas: vec![1, 3],
+                diagonal: true,
+            },
+        ];
+        for rules in &variants {
+            let maps = [
+                parse_input(EXAMPLE.as_bytes()),
+                generate_map(40, 40),
+                generate_map(3, 300),
+                generate_map(300, 3),
+            ];
+            for input in maps {
+                let (scores, ratings) = (scores(&input, rules), ratings(&input, rules));
+                for cell @ (row, col) in cells(&input) {
+                    if input[row][col] != Some(rules.start) {
+                        continue;
+                    }
+                    assert_eq!(scores[row][col], trail_score(&input, rules, cell) as u64);
+                    assert_eq!(ratings[row][col], trail_raiting(&input, rules, cell) as u64);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let downhill = Rules {
+            start: 9,
+            end: 0,
+            deltas: vec![-1],
+            diagonal: false,
+        };
+        // Every trail can be walked backwards.
+        assert_eq!(part_2(&input, &downhill), 81);
+        assert_eq!(downhill.validate(), Ok(()));
+        assert_eq!(
+            Rules {
+                deltas: vec![],
+                ..Rules::default()
+            }
+            .validate(),
+            Err(InvalidRules::NoDeltas)
+        );
+        assert_eq!(
+            Rules {
+                end: 0,
+                ..Rules::default()
+            }
+            .validate(),
+            Err(InvalidRules::StartIsEnd)
+        );
+        assert_eq!(
+            Rules {
+                end: 10,
+                ..Rules::default()
+            }
+            .validate(),
+            Err(InvalidRules::HeightOutOfRange { height: 10 })
+        );
+        assert_eq!(
+            Rules {
+                deltas: vec![1, -1],
+                ..Rules::default()
+            }
+            .validate(),
+            Err(InvalidRules::AwayFromEnd { delta: -1 })
+        );
     }
 }
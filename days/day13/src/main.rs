@@ -4,13 +4,21 @@ use regex::Regex;
 
 fn main() {
     let input = parse_input(stdin().lock());
+    if std::env::args().any(|arg| arg == "--witness") {
+        println!("part 1:");
+        print_results(&machine_results(&input, 0));
+        println!("part 2:");
+        print_results(&machine_results(&input, PART_2_PRIZE_OFFSET));
+    }
     println!("part 1: {}", part_1(&input));
     println!("part 2: {}", part_2(&input));
 }
 
 type Input = Vec<ClawMachine>;
 
-#[derive(Debug)]
+const PART_2_PRIZE_OFFSET: u128 = 10000000000000;
+
+#[derive(Debug, Clone, Copy)]
 struct ClawMachine {
     a_button: (u128, u128),
     b_button: (u128, u128),
@@ -43,36 +51,92 @@ fn parse_input(mut input: impl BufRead) -> Input {
         .collect()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MachineResult {
+    Winnable { a_presses: u128, b_presses: u128 },
+    Unwinnable,
+}
+
+impl MachineResult {
+    fn tokens(&self) -> Option<u128> {
+        match self {
+            Self::Winnable {
+                a_presses,
+                b_presses,
+            } => Some(a_presses * 3 + b_presses),
+            Self::Unwinnable => None,
+        }
+    }
+}
+
 fn part_1(input: &Input) -> u128 {
-    input.iter().filter_map(tokens_needed_to_win).sum()
+    machine_results(input, 0)
+        .iter()
+        .filter_map(MachineResult::tokens)
+        .sum()
 }
 
 fn part_2(input: &Input) -> u128 {
-    input
+    machine_results(input, PART_2_PRIZE_OFFSET)
         .iter()
-        .map(
-            |ClawMachine {
-                 a_button,
-                 b_button,
-                 prize,
-             }| ClawMachine {
-                a_button: *a_button,
-                b_button: *b_button,
-                prize: (prize.0 + 10000000000000, prize.1 + 10000000000000),
-            },
-        )
-        .filter_map(|machine| tokens_needed_to_win(&machine))
+        .filter_map(MachineResult::tokens)
         .sum()
 }
 
-fn tokens_needed_to_win(machine: &ClawMachine) -> Option<u128> {
+fn machine_results(input: &Input, prize_offset: u128) -> Vec<MachineResult> {
+    input
+        .iter()
+        .map(|machine| ClawMachine {
+            prize: (
+                machine.prize.0 + prize_offset,
+                machine.prize.1 + prize_offset,
+            ),
+            ..*machine
+        })
+        .map(|machine| solve(&machine))
+        .collect()
+}
+
+fn print_results(results: &[MachineResult]) {
+    println!(
+        "{:>8} {:>10} {:>16} {:>16} {:>18}",
+        "machine", "winnable", "a presses", "b presses", "tokens"
+    );
+    for (i, result) in results.iter().enumerate() {
+        match result {
+            MachineResult::Winnable {
+                a_presses,
+                b_presses,
+            } => println!(
+                "{:>8} {:>10} {:>16} {:>16} {:>18}",
+                i + 1,
+                "yes",
+                a_presses,
+                b_presses,
+                result.tokens().unwrap()
+            ),
+            MachineResult::Unwinnable => {
+                println!(
+                    "{:>8} {:>10} {:>16} {:>16} {:>18}",
+                    i + 1,
+                    "no",
+                    "-",
+                    "-",
+                    "-"
+                )
+            }
+        }
+    }
+}
+
+fn solve(machine: &ClawMachine) -> MachineResult {
     let a_slope = machine.a_button.1 as f64 / machine.a_button.0 as f64;
     let b_slope = machine.b_button.1 as f64 / machine.b_button.0 as f64;
     let max_a_presses =
         (machine.prize.0 / machine.a_button.0).min(machine.prize.1 / machine.a_button.1);
     let can_be_reached_with_bs = |distance_remaining: (u128, u128)| -> bool {
-        distance_remaining.0 % machine.b_button.0 == 0
-            && distance_remaining.1 % machine.b_button.1 == 0
+        distance_remaining.0.is_multiple_of(machine.b_button.0)
+            && distance_remaining.1.is_multiple_of(machine.b_button.1)
             && distance_remaining.0 / machine.b_button.0
                 == distance_remaining.1 / machine.b_button.1
     };
@@ -82,7 +146,10 @@ fn tokens_needed_to_win(machine: &ClawMachine) -> Option<u128> {
         rise as f64 / run as f64
     };
     if can_be_reached_with_bs(machine.prize) {
-        return Some((machine.prize.0 / machine.b_button.0) as u128);
+        return MachineResult::Winnable {
+            a_presses: 0,
+            b_presses: machine.prize.0 / machine.b_button.0,
+        };
     }
     let mut low = 0;
     let mut high = max_a_presses;
@@ -93,11 +160,17 @@ fn tokens_needed_to_win(machine: &ClawMachine) -> Option<u128> {
             machine.a_button.1 * a_presses,
         );
         if position == machine.prize {
-            return Some(a_presses * 3);
+            return MachineResult::Winnable {
+                a_presses,
+                b_presses: 0,
+            };
         }
         let distance_remaining = (machine.prize.0 - position.0, machine.prize.1 - position.1);
         if can_be_reached_with_bs(distance_remaining) {
-            return Some(a_presses * 3 + (distance_remaining.0 / machine.b_button.0) as u128);
+            return MachineResult::Winnable {
+                a_presses,
+                b_presses: distance_remaining.0 / machine.b_button.0,
+            };
         }
         let remaining_slope = slope_to_prize(position);
         if (a_slope < b_slope && remaining_slope > b_slope)
@@ -111,7 +184,7 @@ fn tokens_needed_to_win(machine: &ClawMachine) -> Option<u128> {
             low = a_presses + 1;
         }
     }
-    return None;
+    MachineResult::Unwinnable
 }
 
 #[cfg(test)]
@@ -130,4 +203,33 @@ mod tests {
         let input = parse_input(EXAMPLE.as_bytes());
         assert_eq!(part_2(&input), 875318608908);
     }
+
+    #[test]
+    fn test_part_1_example_winnable_machines() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let results = machine_results(&input, 0);
+        let winnable: Vec<usize> = results
+            .iter()
+            .enumerate()
+            .filter(|(_, result)| result.tokens().is_some())
+            .map(|(i, _)| i + 1)
+            .collect();
+        assert_eq!(winnable, vec![1, 3]);
+        assert_eq!(
+            results[0],
+            MachineResult::Winnable {
+                a_presses: 80,
+                b_presses: 40
+            }
+        );
+        assert_eq!(results[0].tokens(), Some(280));
+        assert_eq!(
+            results[2],
+            MachineResult::Winnable {
+                a_presses: 38,
+                b_presses: 86
+            }
+        );
+        assert_eq!(results[2].tokens(), Some(200));
+    }
 }
@@ -1,50 +1,403 @@
-use std::io::{stdin, BufRead};
-
-use regex::Regex;
+use std::{
+    io::{stdin, BufRead, Read},
+    ops::{Range, RangeInclusive},
+};
 
 fn main() {
-    let input = parse_input(stdin().lock());
+    let input = if std::env::args().any(|arg| arg == "--highlight") {
+        let mut input = Vec::new();
+        stdin().lock().read_to_end(&mut input).unwrap();
+        println!("{}", render_highlighted(&input, &trace(input.as_slice())));
+        parse_input(input.as_slice())
+    } else {
+        parse_input(stdin().lock())
+    };
     println!("part 1: {}", part_1(&input));
     println!("part 2: {}", part_2(&input));
 }
 
-type Input = String;
+// Both parts' sums, since the input is only read once and never held in
+// memory.
+type Input = (u64, u64);
 
-fn parse_input(mut input: impl BufRead) -> Input {
-    let mut s = String::new();
-    input.read_to_string(&mut s).unwrap();
-    s
+fn parse_input(input: impl BufRead) -> Input {
+    evaluate(input)
 }
 
 fn part_1(input: &Input) -> u64 {
-    let regex = Regex::new(r"mul\(([0-9]{1,3}),([0-9]{1,3})\)").unwrap();
-    regex
-        .captures_iter(input)
-        .map(|cap| cap.extract())
-        .map(|(_, [x, y])| x.parse::<u64>().unwrap() * y.parse::<u64>().unwrap())
-        .sum()
+    input.0
 }
 
 fn part_2(input: &Input) -> u64 {
-    let regex = Regex::new(r"mul\([0-9]{1,3},[0-9]{1,3}\)|don't\(\)|do\(\)").unwrap();
-    let capture_regex = Regex::new(r"mul\(([0-9]{1,3}),([0-9]{1,3})\)").unwrap();
-    let mut enabled = true;
-    let mut sum = 0;
-    for m in regex.find_iter(input).map(|m| m.as_str()) {
-        if m == "don't()" {
-            enabled = false
-        } else if m == "do()" {
-            enabled = true
-        } else if enabled {
-            let (_, [x, y]) = capture_regex.captures(m).unwrap().extract();
-            sum += x.parse::<u64>().unwrap() * y.parse::<u64>().unwrap()
+    input.1
+}
+
+fn evaluate(input: impl BufRead) -> (u64, u64) {
+    let instruction_set = InstructionSet::standard();
+    let mut part_1 = Interpreter::ignoring_conditionals();
+    let mut part_2 = Interpreter::default();
+    let lexer = Lexer::new(input, instruction_set.max_name_len());
+    for instruction in Parser::new(lexer, &instruction_set) {
+        part_1.execute(&instruction);
+        part_2.execute(&instruction);
+    }
+    (part_1.sum, part_2.sum)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TraceEntry {
+    instruction: Instruction,
+    enabled: bool,
+    contribution: u64,
+}
+
+fn trace(input: impl BufRead) -> Vec<TraceEntry> {
+    let instruction_set = InstructionSet::standard();
+    let mut interpreter = Interpreter::default();
+    let lexer = Lexer::new(input, instruction_set.max_name_len());
+    Parser::new(lexer, &instruction_set)
+        .map(|instruction| {
+            let enabled = interpreter.enabled;
+            let contribution = interpreter.execute(&instruction);
+            TraceEntry {
+                instruction,
+                enabled,
+                contribution,
+            }
+        })
+        .collect()
+}
+
+fn render_highlighted(input: &[u8], trace: &[TraceEntry]) -> String {
+    const COUNTED: &str = "\x1b[1;32m";
+    const DISABLED: &str = "\x1b[2;31m";
+    const CONDITIONAL: &str = "\x1b[1;33m";
+    const RESET: &str = "\x1b[0m";
+    let mut out = String::new();
+    let mut pos = 0;
+    for entry in trace {
+        let span = &entry.instruction.span;
+        let colour = match entry.instruction.opcode {
+            Opcode::Do | Opcode::Dont => CONDITIONAL,
+            Opcode::Mul if entry.enabled => COUNTED,
+            Opcode::Mul => DISABLED,
+        };
+        out.push_str(&String::from_utf8_lossy(&input[pos..span.start]));
+        out.push_str(colour);
+        out.push_str(&String::from_utf8_lossy(&input[span.clone()]));
+        out.push_str(RESET);
+        pos = span.end;
+    }
+    out.push_str(&String::from_utf8_lossy(&input[pos..]));
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Number { value: u64, digits: usize },
+    OpenParen,
+    CloseParen,
+    Comma,
+    Other,
+}
+
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_' || b == b'\''
+}
+
+// Reads one byte at a time from the underlying buffer, so tokens (and hence
+// instructions) that straddle a buffer boundary are lexed like any other.
+struct Lexer<R> {
+    input: R,
+    max_word_len: usize,
+    pos: usize,
+}
+
+impl<R: BufRead> Lexer<R> {
+    fn new(input: R, max_word_len: usize) -> Self {
+        Self {
+            input,
+            max_word_len,
+            pos: 0,
         }
     }
-    return sum;
+
+    fn peek(&mut self) -> Option<u8> {
+        self.input.fill_buf().unwrap().first().copied()
+    }
+
+    fn bump(&mut self) {
+        self.input.consume(1);
+        self.pos += 1;
+    }
+
+    fn lex_word(&mut self) -> Token {
+        // Only the tail of a word can name an instruction, so long runs of
+        // corrupted letters are truncated to keep memory bounded.
+        let mut word = Vec::new();
+        while let Some(b) = self.peek().filter(|&b| is_word_byte(b)) {
+            if word.len() == self.max_word_len {
+                word.remove(0);
+            }
+            word.push(b);
+            self.bump();
+        }
+        Token::Word(String::from_utf8_lossy(&word).into_owned())
+    }
+
+    fn lex_number(&mut self) -> Token {
+        let mut value = 0u64;
+        let mut digits = 0;
+        while let Some(b) = self.peek().filter(|b| b.is_ascii_digit()) {
+            value = value.saturating_mul(10).saturating_add((b - b'0') as u64);
+            digits += 1;
+            self.bump();
+        }
+        Token::Number { value, digits }
+    }
+}
+
+impl<R: BufRead> Iterator for Lexer<R> {
+    type Item = (Token, Range<usize>);
+
+    fn next(&mut self) -> Option<(Token, Range<usize>)> {
+        let start = self.pos;
+        let b = self.peek()?;
+        let token = if is_word_byte(b) {
+            self.lex_word()
+        } else if b.is_ascii_digit() {
+            self.lex_number()
+        } else {
+            self.bump();
+            match b {
+                b'(' => Token::OpenParen,
+                b')' => Token::CloseParen,
+                b',' => Token::Comma,
+                _ => Token::Other,
+            }
+        };
+        Some((token, start..self.pos))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Opcode {
+    Mul,
+    Do,
+    Dont,
+}
+
+// How many operands an instruction takes, and how many digits each may have.
+struct Operands {
+    count: usize,
+    digits: RangeInclusive<usize>,
+}
+
+struct InstructionDef {
+    name: &'static str,
+    opcode: Opcode,
+    // `None` for instructions called with empty parentheses.
+    operands: Option<Operands>,
+}
+
+impl InstructionDef {
+    fn arity(&self) -> usize {
+        self.operands.as_ref().map_or(0, |operands| operands.count)
+    }
+
+    fn accepts_operand(&self, digits: usize) -> bool {
+        self.operands
+            .as_ref()
+            .is_some_and(|operands| operands.digits.contains(&digits))
+    }
+}
+
+struct InstructionSet {
+    defs: Vec<InstructionDef>,
+}
+
+impl InstructionSet {
+    fn standard() -> Self {
+        Self {
+            defs: vec![
+                InstructionDef {
+                    name: "mul",
+                    opcode: Opcode::Mul,
+                    operands: Some(Operands {
+                        count: 2,
+                        digits: 1..=3,
+                    }),
+                },
+                InstructionDef {
+                    name: "do",
+                    opcode: Opcode::Do,
+                    operands: None,
+                },
+                InstructionDef {
+                    name: "don't",
+                    opcode: Opcode::Dont,
+                    operands: None,
+                },
+            ],
+        }
+    }
+
+    fn max_name_len(&self) -> usize {
+        self.defs
+            .iter()
+            .map(|def| def.name.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Instructions may be preceded by arbitrary corrupted letters (e.g.
+    // `xmul(2,4)`), so a word names an instruction if it ends with that name.
+    fn lookup(&self, word: &str) -> Option<&InstructionDef> {
+        self.defs
+            .iter()
+            .filter(|def| word.ends_with(def.name))
+            .max_by_key(|def| def.name.len())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Instruction {
+    opcode: Opcode,
+    operands: Vec<u64>,
+    span: Range<usize>,
+}
+
+struct PartialCall<'s> {
+    def: &'s InstructionDef,
+    start: usize,
+    operands: Vec<u64>,
+}
+
+enum ParseState<'s> {
+    Idle,
+    Named(PartialCall<'s>),
+    Operand(PartialCall<'s>),
+    Separator(PartialCall<'s>),
+}
+
+struct Parser<'s, I> {
+    tokens: I,
+    instruction_set: &'s InstructionSet,
+    state: ParseState<'s>,
+    pending: Option<(Token, Range<usize>)>,
+}
+
+impl<'s, I: Iterator<Item = (Token, Range<usize>)>> Parser<'s, I> {
+    fn new(tokens: I, instruction_set: &'s InstructionSet) -> Self {
+        Self {
+            tokens,
+            instruction_set,
+            state: ParseState::Idle,
+            pending: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = (Token, Range<usize>)>> Iterator for Parser<'_, I> {
+    type Item = Instruction;
+
+    fn next(&mut self) -> Option<Instruction> {
+        loop {
+            let (token, span) = self.pending.take().or_else(|| self.tokens.next())?;
+            let state = std::mem::replace(&mut self.state, ParseState::Idle);
+            match (state, token) {
+                (ParseState::Idle, Token::Word(word)) => {
+                    if let Some(def) = self.instruction_set.lookup(&word) {
+                        self.state = ParseState::Named(PartialCall {
+                            def,
+                            start: span.end - def.name.len(),
+                            operands: Vec::new(),
+                        });
+                    }
+                }
+                (ParseState::Idle, _) => {}
+                (ParseState::Named(call), Token::OpenParen) => {
+                    self.state = match call.def.operands {
+                        None => ParseState::Separator(call),
+                        Some(_) => ParseState::Operand(call),
+                    };
+                }
+                (ParseState::Operand(mut call), Token::Number { value, digits })
+                    if call.def.accepts_operand(digits) =>
+                {
+                    call.operands.push(value);
+                    self.state = ParseState::Separator(call);
+                }
+                (ParseState::Separator(call), Token::Comma)
+                    if call.operands.len() < call.def.arity() =>
+                {
+                    self.state = ParseState::Operand(call);
+                }
+                (ParseState::Separator(call), Token::CloseParen)
+                    if call.operands.len() == call.def.arity() =>
+                {
+                    return Some(Instruction {
+                        opcode: call.def.opcode,
+                        operands: call.operands,
+                        span: call.start..span.end,
+                    });
+                }
+                // A malformed call may be immediately followed by a valid one,
+                // so the token that broke it is re-read from the idle state.
+                (_, token) => self.pending = Some((token, span)),
+            }
+        }
+    }
+}
+
+struct Interpreter {
+    enabled: bool,
+    conditionals: bool,
+    sum: u64,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            conditionals: true,
+            sum: 0,
+        }
+    }
+}
+
+impl Interpreter {
+    fn ignoring_conditionals() -> Self {
+        Self {
+            conditionals: false,
+            ..Self::default()
+        }
+    }
+
+    fn execute(&mut self, instruction: &Instruction) -> u64 {
+        let contribution = match instruction.opcode {
+            Opcode::Do | Opcode::Dont if !self.conditionals => 0,
+            Opcode::Do => {
+                self.enabled = true;
+                0
+            }
+            Opcode::Dont => {
+                self.enabled = false;
+                0
+            }
+            Opcode::Mul if self.enabled => instruction.operands.iter().product(),
+            Opcode::Mul => 0,
+        };
+        self.sum += contribution;
+        contribution
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::BufReader;
+
     use super::*;
     const EXAMPLE: &str = include_str!("../example.txt");
     const EXAMPLE2: &str = include_str!("../example2.txt");
@@ -60,4 +413,74 @@ mod tests {
         let input = parse_input(EXAMPLE2.as_bytes());
         assert_eq!(part_2(&input), 48);
     }
+
+    #[test]
+    fn test_instructions_straddling_buffer_boundaries() {
+        for capacity in 1..=EXAMPLE2.len() {
+            let input = BufReader::with_capacity(capacity, EXAMPLE2.as_bytes());
+            assert_eq!(evaluate(input), (161, 48), "buffer capacity {capacity}");
+        }
+    }
+
+    #[test]
+    fn test_long_corrupted_words_are_truncated() {
+        let input = format!("{}mul(2,3)", "x".repeat(10_000));
+        let set = InstructionSet::standard();
+        let mut lexer = Lexer::new(input.as_bytes(), set.max_name_len());
+        assert_eq!(
+            lexer.next(),
+            Some((Token::Word("xxmul".to_string()), 0..10_003))
+        );
+        assert_eq!(evaluate(input.as_bytes()), (6, 6));
+    }
+
+    #[test]
+    fn test_parser_rejects_malformed_calls() {
+        let set = InstructionSet::standard();
+        let input: &[u8] = b"mul(1234,5)mul(1,2mul(3,4)do(1)don't()";
+        let parsed: Vec<Instruction> =
+            Parser::new(Lexer::new(input, set.max_name_len()), &set).collect();
+        assert_eq!(
+            parsed,
+            vec![
+                Instruction {
+                    opcode: Opcode::Mul,
+                    operands: vec![3, 4],
+                    span: 18..26
+                },
+                Instruction {
+                    opcode: Opcode::Dont,
+                    operands: vec![],
+                    span: 31..38
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trace_part_2_example() {
+        let summary: Vec<(Range<usize>, Opcode, bool, u64)> = trace(EXAMPLE2.as_bytes())
+            .into_iter()
+            .map(|entry| {
+                (
+                    entry.instruction.span,
+                    entry.instruction.opcode,
+                    entry.enabled,
+                    entry.contribution,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1..9, Opcode::Mul, true, 8),
+                (20..27, Opcode::Dont, true, 0),
+                (28..36, Opcode::Mul, false, 0),
+                (48..57, Opcode::Mul, false, 0),
+                (59..63, Opcode::Do, false, 0),
+                (64..72, Opcode::Mul, true, 40),
+            ]
+        );
+        assert_eq!(&EXAMPLE2[48..57], "mul(11,8)");
+    }
 }
@@ -1,112 +1,345 @@
 use std::io::{stdin, BufRead};
 
+use aho_corasick::AhoCorasick;
+
 fn main() {
-    let input = parse_input(stdin().lock());
+    let input = parse_input(stdin().lock()).unwrap();
+    if std::env::args().any(|arg| arg == "--render") {
+        for (part, patterns) in [(1, part_1_patterns()), (2, part_2_patterns())] {
+            let matches = find_all(&input, &patterns);
+            println!("part {part} matches:");
+            println!("{}", render_matches(&input, &patterns, &matches));
+        }
+    }
     println!("part 1: {}", part_1(&input));
     println!("part 2: {}", part_2(&input));
 }
 
 type Input = Vec<Vec<char>>;
 
-fn parse_input(input: impl BufRead) -> Input {
-    input
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    RaggedLine {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+fn parse_input(input: impl BufRead) -> Result<Input, ParseError> {
+    let grid: Input = input
         .lines()
         .map(|line| line.unwrap().chars().collect())
-        .collect()
+        .collect();
+    let expected = grid.first().map(|row| row.len()).unwrap_or(0);
+    if let Some((line, row)) = grid
+        .iter()
+        .enumerate()
+        .find(|(_, row)| row.len() != expected)
+    {
+        return Err(ParseError::RaggedLine {
+            line: line + 1,
+            expected,
+            found: row.len(),
+        });
+    }
+    Ok(grid)
 }
 
+type Position = (usize, usize);
 type Direction = (isize, isize);
+type ShapeCells = Vec<(Position, char)>;
+
+fn part_1_patterns() -> PatternSet {
+    PatternSet {
+        words: vec!["XMAS".chars().collect()],
+        shapes: vec![],
+    }
+}
+
+fn part_2_patterns() -> PatternSet {
+    PatternSet {
+        words: vec![],
+        shapes: vec![Shape::parse(&["M.M", ".A.", "S.S"])],
+    }
+}
 
 fn part_1(input: &Input) -> u64 {
-    let word = &['X', 'M', 'A', 'S'];
-    let mut count_matches = 0;
-    for r in 0..input.len() {
-        for c in 0..input[r].len() {
-            for direction in directions() {
-                if search_in_direction(input, (r as isize, c as isize), direction, word) {
-                    count_matches += 1;
-                }
+    find_all(input, &part_1_patterns()).len() as u64
+}
+
+fn part_2(input: &Input) -> u64 {
+    find_all(input, &part_2_patterns()).len() as u64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Shape {
+    cells: Vec<Vec<Option<char>>>,
+}
+
+impl Shape {
+    fn parse(rows: &[&str]) -> Self {
+        let cells = rows
+            .iter()
+            .map(|row| row.chars().map(|c| (c != '.').then_some(c)).collect())
+            .collect();
+        Self { cells }
+    }
+
+    fn height(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn width(&self) -> usize {
+        self.cells.iter().map(|row| row.len()).max().unwrap_or(0)
+    }
+
+    // The non-wildcard cells as offsets from the top left of the bounding box
+    // once the shape is mirrored left to right (if `reflected`) and then
+    // turned clockwise `quarter_turns` times.
+    fn transformed_cells(
+        &self,
+        Transform {
+            quarter_turns,
+            reflected,
+        }: Transform,
+    ) -> ShapeCells {
+        let (height, width) = (self.height(), self.width());
+        let mut cells: ShapeCells = self
+            .cells
+            .iter()
+            .enumerate()
+            .flat_map(|(r, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter_map(move |(c, cell)| cell.map(|letter| ((r, c), letter)))
+            })
+            .map(|((r, c), letter)| {
+                let c = if reflected { width - 1 - c } else { c };
+                let offset = match quarter_turns % 4 {
+                    0 => (r, c),
+                    1 => (c, height - 1 - r),
+                    2 => (height - 1 - r, width - 1 - c),
+                    _ => (width - 1 - c, r),
+                };
+                (offset, letter)
+            })
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    // One placement per distinct orientation: transforms that map a symmetric
+    // shape onto itself would otherwise report the same occurrence twice.
+    fn distinct_orientations(&self) -> Vec<(Transform, ShapeCells)> {
+        let mut variants: Vec<(Transform, ShapeCells)> = Vec::new();
+        for transform in Transform::all() {
+            let cells = self.transformed_cells(transform);
+            if !variants.iter().any(|(_, seen)| *seen == cells) {
+                variants.push((transform, cells));
             }
         }
+        variants
     }
-    count_matches
 }
 
-fn directions() -> impl Iterator<Item = Direction> {
-    [-1, 0, 1]
-        .into_iter()
-        .flat_map(|r| [-1, 0, 1].into_iter().map(move |c| (r, c)))
-        .filter(|&direction| direction != (0, 0))
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Transform {
+    quarter_turns: u8,
+    reflected: bool,
 }
 
-fn search_in_direction(
-    input: &Input,
-    (r, c): (isize, isize),
-    (r_mod, c_mod): Direction,
-    word: &[char],
-) -> bool {
-    if word.len() == 0 {
-        return true;
-    }
-    if r < 0 || c < 0 || r as usize >= input.len() || c as usize >= input[r as usize].len() {
-        return false;
+impl Transform {
+    fn all() -> impl Iterator<Item = Transform> {
+        [false, true].into_iter().flat_map(|reflected| {
+            (0..4).map(move |quarter_turns| Transform {
+                quarter_turns,
+                reflected,
+            })
+        })
     }
-    if input[r as usize][c as usize] != word[0] {
-        return false;
+}
+
+struct PatternSet {
+    words: Vec<Vec<char>>,
+    shapes: Vec<Shape>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Orientation {
+    Line(Direction),
+    Transformed(Transform),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PatternId {
+    Word(usize),
+    Shape(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Match {
+    pattern: PatternId,
+    start: Position,
+    orientation: Orientation,
+}
+
+impl Match {
+    fn cells(&self, patterns: &PatternSet) -> Vec<Position> {
+        let (r, c) = self.start;
+        match (self.pattern, self.orientation) {
+            (PatternId::Word(word), Orientation::Line((dr, dc))) => {
+                let len = patterns.words[word].len() as isize;
+                (0..len)
+                    .map(|k| {
+                        (
+                            (r as isize + dr * k) as usize,
+                            (c as isize + dc * k) as usize,
+                        )
+                    })
+                    .collect()
+            }
+            (PatternId::Shape(shape), Orientation::Transformed(transform)) => {
+                let cells = patterns.shapes[shape].transformed_cells(transform);
+                cells
+                    .into_iter()
+                    .map(|((dr, dc), _)| (r + dr, c + dc))
+                    .collect()
+            }
+            _ => unreachable!("words are matched along lines and shapes by transforms"),
+        }
     }
-    return search_in_direction(input, (r + r_mod, c + c_mod), (r_mod, c_mod), &word[1..]);
 }
 
-fn part_2(input: &Input) -> u64 {
-    let word = &['M', 'A', 'S'];
-    let mut count_matches = count_x_words(input, word);
-    let mut input: Input = rotate_matrix(input);
-    count_matches += count_x_words(&input, word);
-    input = rotate_matrix(&input);
-    count_matches += count_x_words(&input, word);
-    input = rotate_matrix(&input);
-    count_matches += count_x_words(&input, word);
-    return count_matches;
-}
-
-fn rotate_matrix(input: &Input) -> Input {
-    let mut output: Vec<Vec<char>> = input.clone();
-    for r in 0..input.len() {
-        for c in 0..input[r].len() {
-            output[r][c] = input[c][input[r].len() - r - 1]
+// Draws the grid with every letter outside of a match replaced by `.`, like
+// the illustrations in the puzzle text.
+fn render_matches(input: &Input, patterns: &PatternSet, matches: &[Match]) -> String {
+    let width = input.first().map(|row| row.len()).unwrap_or(0);
+    let mut shown = vec![vec![false; width]; input.len()];
+    for m in matches {
+        for (r, c) in m.cells(patterns) {
+            shown[r][c] = true;
         }
     }
-    output
+    input
+        .iter()
+        .zip(shown)
+        .map(|(row, shown_row)| {
+            row.iter()
+                .zip(shown_row)
+                .map(|(&letter, shown)| if shown { letter } else { '.' })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
-fn count_x_words(input: &Input, word: &[char]) -> u64 {
-    let mut count_matches = 0;
+fn find_all(input: &Input, patterns: &PatternSet) -> Vec<Match> {
+    let mut matches = find_words(input, &patterns.words);
+    matches.extend(find_shapes(input, &patterns.shapes));
+    matches
+}
 
-    for r in 0..input.len() {
-        for c in 0..input[r].len() {
-            if is_x_word_at_pos(input, (r, c), word) {
-                count_matches += 1;
-            }
+// Every straight line through the grid that a word could be read along:
+// rows, columns, diagonals and anti-diagonals, each read in its forward
+// direction. Backwards readings are handled by searching for reversed words.
+fn lines(input: &Input) -> Vec<(Direction, Vec<Position>)> {
+    let height = input.len();
+    let width = input.first().map(|row| row.len()).unwrap_or(0);
+    let walk = |start: Position, (dr, dc): Direction| {
+        let mut line = Vec::new();
+        let (mut r, mut c) = (start.0 as isize, start.1 as isize);
+        while 0 <= r && r < height as isize && 0 <= c && c < width as isize {
+            line.push((r as usize, c as usize));
+            r += dr;
+            c += dc;
         }
+        line
+    };
+    let mut lines = Vec::new();
+    for r in 0..height {
+        lines.push(((0, 1), walk((r, 0), (0, 1))));
+    }
+    for c in 0..width {
+        lines.push(((1, 0), walk((0, c), (1, 0))));
     }
-    count_matches
+    for r in 0..height {
+        lines.push(((1, 1), walk((r, 0), (1, 1))));
+        lines.push(((1, -1), walk((r, width.saturating_sub(1)), (1, -1))));
+    }
+    for c in 1..width {
+        lines.push(((1, 1), walk((0, c), (1, 1))));
+        lines.push(((1, -1), walk((0, c - 1), (1, -1))));
+    }
+    lines
 }
 
-fn is_x_word_at_pos(input: &Input, (r, c): (usize, usize), word: &[char]) -> bool {
-    if r + word.len() > input.len() || c + word.len() > input[r].len() {
-        return false;
+fn find_words(input: &Input, words: &[Vec<char>]) -> Vec<Match> {
+    if words.is_empty() {
+        return Vec::new();
     }
-    let left_to_right_diagonal: Vec<char> = (0..word.len())
-        .map(|delta| (r + delta, c + delta))
-        .map(|(r, c)| input[r][c])
+    let needles: Vec<String> = words
+        .iter()
+        .map(|word| word.iter().collect())
+        .chain(words.iter().map(|word| word.iter().rev().collect()))
         .collect();
+    let automaton = AhoCorasick::new(&needles).unwrap();
+    let mut matches = Vec::new();
+    for ((dr, dc), line) in lines(input) {
+        let haystack: String = line.iter().map(|&(r, c)| input[r][c]).collect();
+        let char_starts: Vec<usize> = haystack.char_indices().map(|(i, _)| i).collect();
+        for found in automaton.find_overlapping_iter(&haystack) {
+            let first = char_starts.binary_search(&found.start()).unwrap();
+            let index = found.pattern().as_usize();
+            let word = index % words.len();
+            let last = first + words[word].len() - 1;
+            let (start, direction) = if index < words.len() {
+                (line[first], (dr, dc))
+            } else {
+                (line[last], (-dr, -dc))
+            };
+            matches.push(Match {
+                pattern: PatternId::Word(word),
+                start,
+                orientation: Orientation::Line(direction),
+            });
+        }
+    }
+    matches
+}
 
-    let right_to_left_diagonal: Vec<char> = (0..word.len())
-        .map(|delta| (r + delta, c + word.len() - 1 - delta))
-        .map(|(r, c)| input[r][c])
+fn find_shapes(input: &Input, shapes: &[Shape]) -> Vec<Match> {
+    let variants: Vec<(usize, Transform, ShapeCells)> = shapes
+        .iter()
+        .enumerate()
+        .flat_map(|(i, shape)| {
+            shape
+                .distinct_orientations()
+                .into_iter()
+                .map(move |(transform, cells)| (i, transform, cells))
+        })
         .collect();
-
-    &left_to_right_diagonal == word && &right_to_left_diagonal == word
+    let mut matches = Vec::new();
+    for r in 0..input.len() {
+        for c in 0..input[r].len() {
+            for (shape, transform, cells) in &variants {
+                let fits = cells.iter().all(|&((dr, dc), letter)| {
+                    input
+                        .get(r + dr)
+                        .and_then(|row| row.get(c + dc))
+                        .is_some_and(|&found| found == letter)
+                });
+                if fits {
+                    matches.push(Match {
+                        pattern: PatternId::Shape(*shape),
+                        start: (r, c),
+                        orientation: Orientation::Transformed(*transform),
+                    });
+                }
+            }
+        }
+    }
+    matches
 }
 
 #[cfg(test)]
@@ -116,33 +349,145 @@ mod tests {
 
     #[test]
     fn test_part_1_example() {
-        let input = parse_input(EXAMPLE.as_bytes());
+        let input = parse_input(EXAMPLE.as_bytes()).unwrap();
         assert_eq!(part_1(&input), 18);
     }
 
     #[test]
     fn test_part_2_example() {
-        let input = parse_input(EXAMPLE.as_bytes());
+        let input = parse_input(EXAMPLE.as_bytes()).unwrap();
         assert_eq!(part_2(&input), 9);
     }
 
     #[test]
-    fn test_rotate_matrix() {
-        let input = vec![
-            vec!['1', '2', '3'],
-            vec!['4', '5', '6'],
-            vec!['7', '8', '9'],
-        ];
-        let expected_output = vec![
-            vec!['3', '6', '9'],
-            vec!['2', '5', '8'],
-            vec!['1', '4', '7'],
-        ];
-        assert_eq!(rotate_matrix(&input), expected_output);
+    fn test_find_all_reports_positions_and_orientations() {
+        let input = parse_input("XMAS\nS.M.\n.A..\nS.MX".as_bytes()).unwrap();
+        let patterns = PatternSet {
+            words: vec!["XMAS".chars().collect(), "XM".chars().collect()],
+            shapes: vec![Shape::parse(&["M.M", ".A.", "S.S"])],
+        };
+        let mut matches = find_all(&input, &patterns);
+        matches.sort_by_key(|m| (m.start, format!("{:?}", m)));
+        let word = |word, start, direction| Match {
+            pattern: PatternId::Word(word),
+            start,
+            orientation: Orientation::Line(direction),
+        };
+        assert_eq!(
+            matches,
+            vec![
+                word(0, (0, 0), (0, 1)),
+                word(1, (0, 0), (0, 1)),
+                Match {
+                    pattern: PatternId::Shape(0),
+                    start: (1, 0),
+                    orientation: Orientation::Transformed(Transform {
+                        quarter_turns: 1,
+                        reflected: false
+                    }),
+                },
+                word(1, (3, 3), (0, -1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rectangular_grids() {
+        let wide = parse_input("M.SXMAS\n.A..MA.\nM.S.M.S".as_bytes()).unwrap();
+        assert_eq!(part_1(&wide), 1);
+        assert_eq!(part_2(&wide), 2);
+        let tall = parse_input("M.S\n.A.\nM.S\nX..\nM..\nA..\nS..".as_bytes()).unwrap();
+        assert_eq!(part_1(&tall), 1);
+        assert_eq!(part_2(&tall), 1);
+    }
+
+    #[test]
+    fn test_ragged_lines_are_rejected() {
+        assert_eq!(
+            parse_input("XMAS\nXMA\nXMAS".as_bytes()),
+            Err(ParseError::RaggedLine {
+                line: 2,
+                expected: 4,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_shape_orientations_respect_symmetry() {
+        let count = |rows: &[&str]| Shape::parse(rows).distinct_orientations().len();
+        assert_eq!(count(&["M.M", ".A.", "S.S"]), 4);
+        assert_eq!(count(&["M.S", ".A.", "M.S"]), 4);
+        assert_eq!(count(&["A"]), 1);
+        assert_eq!(count(&["AB"]), 4);
+        assert_eq!(count(&["AB", "C."]), 8);
+    }
+
+    #[test]
+    fn test_match_cells() {
+        let input = parse_input("XMAS\nS.M.\n.A..\nS.MX".as_bytes()).unwrap();
+        let patterns = PatternSet {
+            words: vec!["XM".chars().collect()],
+            shapes: vec![Shape::parse(&["M.M", ".A.", "S.S"])],
+        };
+        let mut cells: Vec<Vec<Position>> = find_all(&input, &patterns)
+            .iter()
+            .map(|m| m.cells(&patterns))
+            .collect();
+        cells.sort();
+        assert_eq!(
+            cells,
+            vec![
+                vec![(0, 0), (0, 1)],
+                vec![(1, 0), (1, 2), (2, 1), (3, 0), (3, 2)],
+                vec![(3, 3), (3, 2)],
+            ]
+        );
+    }
 
+    #[test]
+    fn test_render_part_1_example() {
+        let input = parse_input(EXAMPLE.as_bytes()).unwrap();
+        let patterns = part_1_patterns();
+        let matches = find_all(&input, &patterns);
+        assert_eq!(
+            render_matches(&input, &patterns, &matches),
+            [
+                "....XXMAS.",
+                ".SAMXMS...",
+                "...S..A...",
+                "..A.A.MS.X",
+                "XMASAMX.MM",
+                "X.....XA.A",
+                "S.S.S.S.SS",
+                ".A.A.A.A.A",
+                "..M.M.M.MM",
+                ".X.X.XMASX",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_part_2_example() {
+        let input = parse_input(EXAMPLE.as_bytes()).unwrap();
+        let patterns = part_2_patterns();
+        let matches = find_all(&input, &patterns);
         assert_eq!(
-            rotate_matrix(&rotate_matrix(&rotate_matrix(&rotate_matrix(&input)))),
-            input
-        )
+            render_matches(&input, &patterns, &matches),
+            [
+                ".M.S......",
+                "..A..MSMS.",
+                ".M.S.MAA..",
+                "..A.ASMSM.",
+                ".M.S.M....",
+                "..........",
+                "S.S.S.S.S.",
+                ".A.A.A.A..",
+                "M.M.M.M.M.",
+                "..........",
+            ]
+            .join("\n")
+        );
     }
 }
@@ -1,13 +1,18 @@
 use std::{
-    cmp::Ordering,
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     io::{stdin, BufRead},
 };
 
 fn main() {
-    let input = parse_input(stdin().lock());
+    let input = parse_input(stdin().lock()).unwrap();
+    if std::env::args().any(|arg| arg == "--diagnose") {
+        print_diagnostics(&input);
+    }
     println!("part 1: {}", part_1(&input));
-    println!("part 2: {}", part_2(&input));
+    match part_2(&input) {
+        Ok(sum) => println!("part 2: {sum}"),
+        Err(cycle) => println!("part 2: no order, rules form a cycle: {cycle}"),
+    }
 }
 
 #[derive(Default)]
@@ -16,23 +21,86 @@ struct Input {
     lines: Vec<Vec<u32>>,
 }
 
-fn parse_input(input: impl BufRead) -> Input {
+// Line numbers in errors are 1-based, matching what an editor shows.
+#[derive(Debug, PartialEq, Eq)]
+enum ParseError {
+    MalformedRule { line: usize, text: String },
+    DuplicateRule { line: usize, rule: (u32, u32) },
+    SelfReferentialRule { line: usize, page: u32 },
+    MissingSeparator,
+    RuleAfterUpdates { line: usize },
+    UnexpectedBlankLine { line: usize },
+    MalformedUpdate { line: usize, text: String },
+    EvenLengthUpdate { line: usize, len: usize },
+    DuplicatePage { line: usize, page: u32 },
+}
+
+enum Section {
+    Rules,
+    Updates,
+}
+
+fn parse_input(input: impl BufRead) -> Result<Input, ParseError> {
     let mut parsed = Input::default();
-    input.lines().map(|line| line.unwrap()).for_each(|line| {
-        if line.is_empty() {
-            return;
-        }
-        if line.contains("|") {
-            let (before, after) = line.split_once('|').unwrap();
-            let (before, after) = (before.parse().unwrap(), after.parse().unwrap());
-            let entry = parsed.rules.entry(before).or_insert(HashSet::new());
-            entry.insert(after);
-        } else {
-            let nums = line.split(',').map(|num| num.parse().unwrap()).collect();
-            parsed.lines.push(nums);
+    let mut section = Section::Rules;
+    for (i, text) in input.lines().map(|line| line.unwrap()).enumerate() {
+        let line = i + 1;
+        match section {
+            Section::Rules if text.is_empty() => section = Section::Updates,
+            Section::Rules => {
+                let (before, after) =
+                    parse_rule(&text).ok_or_else(|| ParseError::MalformedRule {
+                        line,
+                        text: text.clone(),
+                    })?;
+                if before == after {
+                    return Err(ParseError::SelfReferentialRule { line, page: before });
+                }
+                if !parsed.rules.entry(before).or_default().insert(after) {
+                    return Err(ParseError::DuplicateRule {
+                        line,
+                        rule: (before, after),
+                    });
+                }
+            }
+            Section::Updates if text.is_empty() => {
+                return Err(ParseError::UnexpectedBlankLine { line })
+            }
+            Section::Updates if parse_rule(&text).is_some() => {
+                return Err(ParseError::RuleAfterUpdates { line })
+            }
+            Section::Updates => {
+                let pages: Vec<u32> = text
+                    .split(',')
+                    .map(|page| page.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| ParseError::MalformedUpdate {
+                        line,
+                        text: text.clone(),
+                    })?;
+                let mut seen = HashSet::new();
+                if let Some(&page) = pages.iter().find(|&&page| !seen.insert(page)) {
+                    return Err(ParseError::DuplicatePage { line, page });
+                }
+                if pages.len().is_multiple_of(2) {
+                    return Err(ParseError::EvenLengthUpdate {
+                        line,
+                        len: pages.len(),
+                    });
+                }
+                parsed.lines.push(pages);
+            }
         }
-    });
-    parsed
+    }
+    match section {
+        Section::Rules => Err(ParseError::MissingSeparator),
+        Section::Updates => Ok(parsed),
+    }
+}
+
+fn parse_rule(text: &str) -> Option<(u32, u32)> {
+    let (before, after) = text.split_once('|')?;
+    Some((before.parse().ok()?, after.parse().ok()?))
 }
 
 fn part_1(input: &Input) -> u64 {
@@ -54,33 +122,305 @@ fn is_valid_line(line: &[u32], rules: &HashMap<u32, HashSet<u32>>) -> bool {
             cannot_be_before.extend(after_set);
         }
     }
-    return true;
+    true
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Violation {
+    rule: (u32, u32),
+    before_index: usize,
+    after_index: usize,
+}
+
+fn violations(line: &[u32], rules: &HashMap<u32, HashSet<u32>>) -> Vec<Violation> {
+    let mut found = Vec::new();
+    for (after_index, &after) in line.iter().enumerate() {
+        for (before_index, &before) in line.iter().enumerate().skip(after_index + 1) {
+            if rules.get(&before).is_some_and(|set| set.contains(&after)) {
+                found.push(Violation {
+                    rule: (before, after),
+                    before_index,
+                    after_index,
+                });
+            }
+        }
+    }
+    found
+}
+
+// Take `page` out of the update at `from`, then insert it so that it ends up
+// at index `to`.
+#[derive(Debug, PartialEq, Eq)]
+struct Move {
+    page: u32,
+    from: usize,
+    to: usize,
+}
+
+// The fewest moves that put `line` into `target` order: pages on a longest
+// subsequence already in target order stay put, every other page is moved
+// once, in target order, to just after the page that precedes it.
+fn repair_plan(line: &[u32], target: &[u32]) -> Vec<Move> {
+    let rank: HashMap<u32, usize> = target.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+    let ranks: Vec<usize> = line.iter().map(|page| rank[page]).collect();
+    let mut longest = vec![1; ranks.len()];
+    let mut previous = vec![None; ranks.len()];
+    for j in 0..ranks.len() {
+        for i in 0..j {
+            if ranks[i] < ranks[j] && longest[i] + 1 > longest[j] {
+                longest[j] = longest[i] + 1;
+                previous[j] = Some(i);
+            }
+        }
+    }
+    let mut keep = HashSet::new();
+    let mut next = (0..ranks.len()).max_by_key(|&i| (longest[i], std::cmp::Reverse(i)));
+    while let Some(i) = next {
+        keep.insert(line[i]);
+        next = previous[i];
+    }
+
+    let mut current = line.to_vec();
+    let mut plan = Vec::new();
+    for (i, &page) in target.iter().enumerate() {
+        if keep.contains(&page) {
+            continue;
+        }
+        let from = current.iter().position(|&p| p == page).unwrap();
+        current.remove(from);
+        let to = match i {
+            0 => 0,
+            _ => current.iter().position(|&p| p == target[i - 1]).unwrap() + 1,
+        };
+        current.insert(to, page);
+        plan.push(Move { page, from, to });
+    }
+    plan
+}
+
+// The order to repair `line` into with the fewest moves. The pages that stay
+// put keep their relative order, so the rules mustn't put any of them the
+// other way round, even by way of other pages. Pages out of order like that
+// form a partial order, so by Dilworth's and König's theorems the most pages
+// that can stay put is a largest antichain of it, found from a maximum
+// matching. The moved pages then go wherever the rules allow around them.
+fn repair_order(line: &[u32], rules: &HashMap<u32, HashSet<u32>>) -> Result<Vec<u32>, RuleCycle> {
+    order_update(line, rules)?;
+    let n = line.len();
+    // Whether the rules put `line[i]` before `line[j]`, directly or not.
+    let mut before: Vec<Vec<bool>> = line
+        .iter()
+        .map(|page| {
+            line.iter()
+                .map(|other| rules.get(page).is_some_and(|set| set.contains(other)))
+                .collect()
+        })
+        .collect();
+    for k in 0..n {
+        for i in 0..n {
+            if before[i][k] {
+                let via = before[k].clone();
+                for (reach, via) in before[i].iter_mut().zip(via) {
+                    *reach |= via;
+                }
+            }
+        }
+    }
+    let out_of_order = |i: usize, j: usize| i < j && before[j][i];
+
+    // Kuhn's algorithm, matching each page to one later page it must follow.
+    fn augment(
+        i: usize,
+        out_of_order: &impl Fn(usize, usize) -> bool,
+        partner: &mut [Option<usize>],
+        visited: &mut [bool],
+    ) -> bool {
+        for j in 0..partner.len() {
+            if out_of_order(i, j) && !visited[j] {
+                visited[j] = true;
+                if partner[j].is_none_or(|k| augment(k, out_of_order, partner, visited)) {
+                    partner[j] = Some(i);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+    let mut partner = vec![None; n];
+    for i in 0..n {
+        augment(i, &out_of_order, &mut partner, &mut vec![false; n]);
+    }
+
+    // Alternating paths from the unmatched pages pick out the antichain.
+    let mut reached_left: Vec<bool> = (0..n).map(|i| !partner.contains(&Some(i))).collect();
+    let mut reached_right = vec![false; n];
+    let mut stack: Vec<usize> = (0..n).filter(|&i| reached_left[i]).collect();
+    while let Some(i) = stack.pop() {
+        for j in 0..n {
+            if out_of_order(i, j) && !reached_right[j] {
+                reached_right[j] = true;
+                if let Some(k) = partner[j].filter(|&k| !reached_left[k]) {
+                    reached_left[k] = true;
+                    stack.push(k);
+                }
+            }
+        }
+    }
+    let keep: Vec<u32> = (0..n)
+        .filter(|&i| reached_left[i] && !reached_right[i])
+        .map(|i| line[i])
+        .collect();
+
+    let mut constraints = rules.clone();
+    for pair in keep.windows(2) {
+        constraints.entry(pair[0]).or_default().insert(pair[1]);
+    }
+    order_update(line, &constraints).map(|ordered| ordered.pages)
 }
 
-fn part_2(input: &Input) -> u64 {
+fn print_diagnostics(input: &Input) {
+    for (i, line) in input.lines.iter().enumerate() {
+        let found = violations(line, &input.rules);
+        if found.is_empty() {
+            continue;
+        }
+        let pages: Vec<String> = line.iter().map(|page| page.to_string()).collect();
+        println!("update {}: {}", i + 1, pages.join(","));
+        for violation in found {
+            let (before, after) = violation.rule;
+            println!(
+                "  violates {before}|{after}: {before} at {}, {after} at {}",
+                violation.before_index, violation.after_index
+            );
+        }
+        match repair_order(line, &input.rules) {
+            Ok(target) => {
+                for Move { page, from, to } in repair_plan(line, &target) {
+                    println!("  move {page} from {from} to {to}");
+                }
+            }
+            Err(cycle) => println!("  no repair, rules form a cycle: {cycle}"),
+        }
+    }
+}
+
+// Fails on the first update whose pages can't be ordered at all.
+fn part_2(input: &Input) -> Result<u64, RuleCycle> {
     input
         .lines
         .iter()
         .filter(|line| !is_valid_line(line, &input.rules))
-        .map(|line| {
-            let mut line = line.clone();
-            line.sort_by(|a, b| {
-                let after_a = &input.rules.get(a);
-                let after_b = &input.rules.get(b);
-                if after_a.map(|after| after.contains(b)).unwrap_or(false) {
-                    Ordering::Less
-                } else if after_b.map(|after| after.contains(a)).unwrap_or(false) {
-                    Ordering::Greater
-                } else {
-                    Ordering::Equal
-                }
-            });
-            line
-        })
-        .map(|valid_line| valid_line[valid_line.len() / 2] as u64)
+        .map(|line| order_update(line, &input.rules).map(|ordered| ordered.pages))
+        .map(|valid_line| valid_line.map(|line| line[line.len() / 2] as u64))
         .sum()
 }
 
+#[derive(Debug, PartialEq, Eq)]
+struct OrderedUpdate {
+    pages: Vec<u32>,
+    // More than one order satisfies the rules, so `pages` is only one of them.
+    ambiguous: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct RuleCycle {
+    rules: Vec<(u32, u32)>,
+}
+
+impl std::fmt::Display for RuleCycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rules: Vec<String> = self
+            .rules
+            .iter()
+            .map(|(before, after)| format!("{before}|{after}"))
+            .collect();
+        write!(f, "{}", rules.join(", "))
+    }
+}
+
+// `line` mustn't repeat a page, which `parse_input` makes sure of.
+fn order_update(
+    line: &[u32],
+    rules: &HashMap<u32, HashSet<u32>>,
+) -> Result<OrderedUpdate, RuleCycle> {
+    let pages: HashSet<u32> = line.iter().copied().collect();
+    // Successors are visited in update order so the result is deterministic.
+    let after = |page: u32| {
+        line.iter()
+            .copied()
+            .filter(move |after| rules.get(&page).is_some_and(|set| set.contains(after)))
+    };
+    let mut in_degree: HashMap<u32, usize> = line.iter().map(|&page| (page, 0)).collect();
+    for &page in &pages {
+        for next in after(page) {
+            *in_degree.get_mut(&next).unwrap() += 1;
+        }
+    }
+
+    let mut ready: VecDeque<u32> = line
+        .iter()
+        .copied()
+        .filter(|page| in_degree[page] == 0)
+        .collect();
+    let mut ordered = Vec::new();
+    let mut ambiguous = false;
+    while let Some(page) = ready.pop_front() {
+        ambiguous |= !ready.is_empty();
+        ordered.push(page);
+        for next in after(page) {
+            let degree = in_degree.get_mut(&next).unwrap();
+            *degree -= 1;
+            if *degree == 0 {
+                ready.push_back(next);
+            }
+        }
+    }
+
+    if ordered.len() < pages.len() {
+        let remaining: HashSet<u32> = in_degree
+            .into_iter()
+            .filter(|&(_, degree)| degree > 0)
+            .map(|(page, _)| page)
+            .collect();
+        return Err(find_cycle(&remaining, rules));
+    }
+    Ok(OrderedUpdate {
+        pages: ordered,
+        ambiguous,
+    })
+}
+
+// Every page left over by Kahn's algorithm has a predecessor that was also
+// left over, so walking predecessors from any of them must revisit a page.
+fn find_cycle(remaining: &HashSet<u32>, rules: &HashMap<u32, HashSet<u32>>) -> RuleCycle {
+    let predecessor = |page: u32| {
+        let mut candidates: Vec<u32> = remaining
+            .iter()
+            .copied()
+            .filter(|before| rules.get(before).is_some_and(|after| after.contains(&page)))
+            .collect();
+        candidates.sort();
+        candidates[0]
+    };
+    let mut path = vec![*remaining.iter().min().unwrap()];
+    loop {
+        let before = predecessor(*path.last().unwrap());
+        if let Some(start) = path.iter().position(|&page| page == before) {
+            let mut cycle: Vec<u32> = path[start..].to_vec();
+            cycle.reverse();
+            let smallest = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+            cycle.rotate_left(smallest);
+            let rules = cycle
+                .iter()
+                .zip(cycle.iter().cycle().skip(1))
+                .map(|(&a, &b)| (a, b))
+                .collect();
+            return RuleCycle { rules };
+        }
+        path.push(before);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,13 +428,156 @@ mod tests {
 
     #[test]
     fn test_part_1_example() {
-        let input = parse_input(EXAMPLE.as_bytes());
+        let input = parse_input(EXAMPLE.as_bytes()).unwrap();
         assert_eq!(part_1(&input), 143);
     }
 
     #[test]
     fn test_part_2_example() {
-        let input = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_2(&input), 123);
+        let input = parse_input(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(part_2(&input), Ok(123));
+    }
+
+    #[test]
+    fn test_order_update_example() {
+        let input = parse_input(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(
+            order_update(&[97, 13, 75, 29, 47], &input.rules),
+            Ok(OrderedUpdate {
+                pages: vec![97, 75, 47, 29, 13],
+                ambiguous: false
+            })
+        );
+    }
+
+    #[test]
+    fn test_order_update_ambiguous() {
+        let input = parse_input("1|2\n1|3\n\n3,2,1".as_bytes()).unwrap();
+        let ordered = order_update(&[3, 2, 1], &input.rules).unwrap();
+        assert_eq!(ordered.pages, vec![1, 3, 2]);
+        assert!(ordered.ambiguous);
+    }
+
+    #[test]
+    fn test_order_update_cycle() {
+        let input = parse_input("1|2\n2|3\n3|1\n3|4\n\n4,3,2,1,5".as_bytes()).unwrap();
+        assert_eq!(
+            order_update(&[4, 3, 2, 1], &input.rules),
+            Err(RuleCycle {
+                rules: vec![(1, 2), (2, 3), (3, 1)]
+            })
+        );
+        let cycle = part_2(&input).unwrap_err();
+        assert_eq!(cycle.to_string(), "1|2, 2|3, 3|1");
+    }
+
+    #[test]
+    fn test_violations_example() {
+        let input = parse_input(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(violations(&[75, 47, 61, 53, 29], &input.rules), vec![]);
+        assert_eq!(
+            violations(&[61, 13, 29], &input.rules),
+            vec![Violation {
+                rule: (29, 13),
+                before_index: 2,
+                after_index: 1
+            }]
+        );
+        assert_eq!(violations(&[97, 13, 75, 29, 47], &input.rules).len(), 4);
+    }
+
+    #[test]
+    fn test_repair_plan() {
+        assert_eq!(
+            repair_plan(&[75, 97, 47, 61, 53], &[97, 75, 47, 61, 53]),
+            vec![Move {
+                page: 97,
+                from: 1,
+                to: 0
+            }]
+        );
+        let line = [97, 13, 75, 29, 47];
+        let target = [97, 75, 47, 29, 13];
+        let plan = repair_plan(&line, &target);
+        assert_eq!(plan.len(), 2);
+        let mut repaired = line.to_vec();
+        for Move { page, from, to } in plan {
+            assert_eq!(repaired.remove(from), page);
+            repaired.insert(to, page);
+        }
+        assert_eq!(repaired, target);
+    }
+
+    #[test]
+    fn test_repair_order() {
+        let input = parse_input(EXAMPLE.as_bytes()).unwrap();
+        for line in &input.lines {
+            let target = repair_order(line, &input.rules).unwrap();
+            assert!(is_valid_line(&target, &input.rules));
+        }
+        // Repairing into the order Kahn's algorithm picks takes two moves,
+        // but moving just 0 keeps the other five pages put.
+        let input = parse_input("0|1\n0|4\n1|2\n1|4\n\n0".as_bytes()).unwrap();
+        let line = [5, 1, 2, 0, 4, 3];
+        let kahn = order_update(&line, &input.rules).unwrap().pages;
+        assert_eq!(repair_plan(&line, &kahn).len(), 2);
+        let target = repair_order(&line, &input.rules).unwrap();
+        let plan = repair_plan(&line, &target);
+        assert_eq!(plan.len(), 1);
+        assert_eq!(plan[0].page, 0);
+        assert!(is_valid_line(&target, &input.rules));
+        // Pages can be out of order by way of a page in between.
+        let input = parse_input("1|2\n2|3\n\n3,1,2".as_bytes()).unwrap();
+        let target = repair_order(&input.lines[0], &input.rules).unwrap();
+        assert_eq!(target, vec![1, 2, 3]);
+        assert_eq!(repair_plan(&input.lines[0], &target).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |text: &str| parse_input(text.as_bytes()).err();
+        assert_eq!(parse("1|2\n\n1,2,3"), None);
+        assert_eq!(
+            parse("1|2\n1,2,3"),
+            Some(ParseError::MalformedRule {
+                line: 2,
+                text: "1,2,3".to_string()
+            })
+        );
+        assert_eq!(parse("1|2\n2|3"), Some(ParseError::MissingSeparator));
+        assert_eq!(
+            parse("1|2\n1|2\n\n1,2,3"),
+            Some(ParseError::DuplicateRule {
+                line: 2,
+                rule: (1, 2)
+            })
+        );
+        assert_eq!(
+            parse("1|1\n\n1,2,3"),
+            Some(ParseError::SelfReferentialRule { line: 1, page: 1 })
+        );
+        assert_eq!(
+            parse("1|2\n\n1,2,3\n2|3"),
+            Some(ParseError::RuleAfterUpdates { line: 4 })
+        );
+        assert_eq!(
+            parse("1|2\n\n1,2,3\n\n1,2,3"),
+            Some(ParseError::UnexpectedBlankLine { line: 4 })
+        );
+        assert_eq!(
+            parse("1|2\n\n1,,3"),
+            Some(ParseError::MalformedUpdate {
+                line: 3,
+                text: "1,,3".to_string()
+            })
+        );
+        assert_eq!(
+            parse("1|2\n\n1,2"),
+            Some(ParseError::EvenLengthUpdate { line: 3, len: 2 })
+        );
+        assert_eq!(
+            parse("1|2\n\n2,1,1"),
+            Some(ParseError::DuplicatePage { line: 3, page: 1 })
+        );
     }
 }
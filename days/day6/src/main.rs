@@ -1,12 +1,31 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     io::{stdin, BufRead},
 };
 
+use rayon::prelude::*;
+
 fn main() {
     let input = parse_input(stdin().lock());
-    println!("part 1: {}", part_1(&input));
-    println!("part 2: {}", part_2(&input));
+    let rules = Rules {
+        turn: if std::env::args().any(|arg| arg == "--turn-left") {
+            Turn::Left
+        } else {
+            Turn::Right
+        },
+        diagonal: std::env::args().any(|arg| arg == "--diagonal"),
+    };
+    let obstacle = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--obstacle=").map(str::to_string))
+        .map(|arg| {
+            let (row, col) = arg.split_once(',').unwrap();
+            (row.parse().unwrap(), col.parse().unwrap())
+        });
+    if obstacle.is_some() || std::env::args().any(|arg| arg == "--path") {
+        print_path(&input, rules, obstacle);
+    }
+    println!("part 1: {}", part_1(&input, rules));
+    println!("part 2: {}", part_2(&input, rules));
 }
 
 type Input = Vec<Vec<char>>;
@@ -14,18 +33,110 @@ type Input = Vec<Vec<char>>;
 type Position = (isize, isize);
 type Direction = (isize, isize);
 
-#[derive(Clone, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
 struct Guard {
     position: Position,
     direction: Direction,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Turn {
+    Right,
+    Left,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Rules {
+    turn: Turn,
+    diagonal: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            turn: Turn::Right,
+            diagonal: false,
+        }
+    }
+}
+
+// Both clockwise from up.
+const CARDINAL: [Direction; 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+const COMPASS: [Direction; 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
+
+impl Rules {
+    fn directions(&self) -> &'static [Direction] {
+        if self.diagonal {
+            &COMPASS
+        } else {
+            &CARDINAL
+        }
+    }
+
+    fn direction_index(&self, direction: Direction) -> usize {
+        self.directions()
+            .iter()
+            .position(|&d| d == direction)
+            .unwrap()
+    }
+
+    fn turn(&self, direction: Direction) -> Direction {
+        let directions = self.directions();
+        let index = self.direction_index(direction);
+        match self.turn {
+            Turn::Right => directions[(index + 1) % directions.len()],
+            Turn::Left => directions[(index + directions.len() - 1) % directions.len()],
+        }
+    }
+}
+
+#[derive(Clone)]
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn contains(&self, i: usize) -> bool {
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    // Returns whether `i` was newly added, like `HashSet::insert`.
+    fn insert(&mut self, i: usize) -> bool {
+        let was_set = self.contains(i);
+        self.words[i / 64] |= 1 << (i % 64);
+        !was_set
+    }
+
+    fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+}
+
 #[derive(Clone)]
 struct Map {
     initial_guard: Guard,
     width: usize,
     height: usize,
-    walls: HashSet<Position>,
+    walls: BitSet,
+    rules: Rules,
 }
 
 impl Map {
@@ -33,79 +144,207 @@ impl Map {
         row >= 0 && col >= 0 && row < self.height as isize && col < self.width as isize
     }
 
+    fn cell_index(&self, (row, col): Position) -> usize {
+        row as usize * self.width + col as usize
+    }
+
+    fn state_index(&self, guard: &Guard) -> usize {
+        let directions = self.rules.directions().len();
+        self.cell_index(guard.position) * directions + self.rules.direction_index(guard.direction)
+    }
+
     fn is_wall(&self, position: Position) -> bool {
-        self.walls.contains(&position)
+        self.is_in_bounds(position) && self.walls.contains(self.cell_index(position))
+    }
+
+    fn with_obstacle(&self, position: Position) -> Self {
+        let mut map = self.clone();
+        if self.is_in_bounds(position) {
+            map.walls.insert(map.cell_index(position));
+        }
+        map
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, PartialEq, Eq)]
+struct Segment {
+    start: Position,
+    direction: Direction,
+    length: usize,
+}
+
+// The guard first repeated the state at `guard_path[entry_index]`, and has
+// been going round the same `period` steps ever since.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct LoopCycle {
+    entry_index: usize,
+    period: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    Exited,
+    Looped(LoopCycle),
+    // Walls on every side the guard may move to, so it can only spin in place.
+    Trapped,
+}
+
+// Steps the guard in place. Besides the path itself, everything is kept in
+// flat bitsets indexed by cell (and by cell and heading for guard states).
 struct GameState {
     map: Map,
     guard_path: Vec<Guard>,
-    prev_guard_states: HashSet<Guard>,
+    visited: BitSet,
+    visited_states: BitSet,
+    outcome: Option<Outcome>,
 }
 
 impl GameState {
     fn new(map: Map) -> Self {
+        let cells = map.width * map.height;
+        let mut visited = BitSet::new(cells);
+        let mut visited_states = BitSet::new(cells * map.rules.directions().len());
+        visited.insert(map.cell_index(map.initial_guard.position));
+        visited_states.insert(map.state_index(&map.initial_guard));
         Self {
-            prev_guard_states: HashSet::new(),
             guard_path: vec![map.initial_guard.clone()],
+            visited,
+            visited_states,
+            outcome: None,
             map,
         }
     }
 
-    fn next(mut self) -> Self {
-        if self.is_game_over() {
-            return self.clone();
+    fn step(&mut self) {
+        if self.outcome.is_some() {
+            return;
         }
-        self.prev_guard_states.insert(self.current_guard().clone());
-        let next_guard = self.next_guard();
-        self.guard_path.push(next_guard);
-        GameState {
-            map: self.map,
-            guard_path: self.guard_path,
-            prev_guard_states: self.prev_guard_states,
+        let Some(next_guard) = self.next_guard() else {
+            self.outcome = Some(Outcome::Trapped);
+            return;
+        };
+        self.guard_path.push(next_guard.clone());
+        if !self.map.is_in_bounds(next_guard.position) {
+            self.outcome = Some(Outcome::Exited);
+            return;
+        }
+        self.visited
+            .insert(self.map.cell_index(next_guard.position));
+        if !self
+            .visited_states
+            .insert(self.map.state_index(&next_guard))
+        {
+            let current_index = self.guard_path.len() - 1;
+            let entry_index = self
+                .guard_path
+                .iter()
+                .position(|guard| *guard == next_guard)
+                .unwrap();
+            self.outcome = Some(Outcome::Looped(LoopCycle {
+                entry_index,
+                period: current_index - entry_index,
+            }));
         }
     }
 
-    fn next_guard(&self) -> Guard {
+    fn run(mut self) -> Self {
+        while self.outcome.is_none() {
+            self.step();
+        }
+        self
+    }
+
+    fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    fn visited_count(&self) -> usize {
+        self.visited.count()
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let mut segments: Vec<Segment> = Vec::new();
+        for step in self.guard_path.windows(2) {
+            match segments.last_mut() {
+                Some(segment) if segment.direction == step[1].direction => segment.length += 1,
+                _ => segments.push(Segment {
+                    start: step[0].position,
+                    direction: step[1].direction,
+                    length: 1,
+                }),
+            }
+        }
+        segments
+    }
+
+    fn turning_points(&self) -> Vec<Position> {
+        self.segments()
+            .iter()
+            .skip(1)
+            .map(|segment| segment.start)
+            .collect()
+    }
+
+    // Draws the route like the puzzle text: `|` and `-` where the guard walked
+    // vertically or horizontally (`\\` and `/` for diagonals), `+` where it
+    // walked along more than one axis, `O` for obstacles given in `highlight`.
+    fn render_path(&self, highlight: &[Position]) -> String {
+        let mut axes: HashMap<Position, HashSet<char>> = HashMap::new();
+        for step in self.guard_path.windows(2) {
+            let axis = match step[1].direction {
+                (0, _) => '-',
+                (_, 0) => '|',
+                (dr, dc) if dr == dc => '\\',
+                _ => '/',
+            };
+            for guard in step {
+                axes.entry(guard.position).or_default().insert(axis);
+            }
+        }
+        let start = &self.map.initial_guard;
+        let mut out = String::new();
+        for row in 0..self.map.height as isize {
+            for col in 0..self.map.width as isize {
+                let pos = (row, col);
+                out.push(if highlight.contains(&pos) {
+                    'O'
+                } else if self.map.is_wall(pos) {
+                    '#'
+                } else if pos == start.position {
+                    guard_char(start.direction)
+                } else {
+                    match axes.get(&pos) {
+                        None => '.',
+                        Some(axes) if axes.len() > 1 => '+',
+                        Some(axes) => *axes.iter().next().unwrap(),
+                    }
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn next_guard(&self) -> Option<Guard> {
         let current_guard = self.current_guard();
         let mut direction = current_guard.direction;
-        for _attempt in 0..4 {
+        for _attempt in 0..self.map.rules.directions().len() {
             let next_pos = apply_movement(current_guard.position, direction);
             if !self.map.is_wall(next_pos) {
-                return Guard {
+                return Some(Guard {
                     position: next_pos,
                     direction,
-                };
+                });
             }
-            direction = turn_right(direction)
+            direction = self.map.rules.turn(direction)
         }
-        panic!("unable to move");
+        None
     }
 
     fn current_guard(&self) -> &Guard {
         self.guard_path.last().unwrap()
     }
 
-    fn is_game_over(&self) -> bool {
-        let current_guard_position = self.current_guard().position;
-        !self.map.is_in_bounds(current_guard_position)
-    }
-
-    fn is_in_loop(&self) -> bool {
-        self.prev_guard_states.contains(self.current_guard())
-    }
-
-    fn guard_visited_positions(&self) -> HashSet<Position> {
-        HashSet::from_iter(
-            self.guard_path[0..self.guard_path.len() - 1]
-                .iter()
-                .map(|guard| guard.position)
-                .filter(|position| self.map.is_in_bounds(*position)),
-        )
-    }
-
     #[allow(unused)]
     fn print(&self) {
         let guard = self.current_guard();
@@ -115,13 +354,7 @@ impl GameState {
                 if self.map.is_wall(pos) {
                     print!("#")
                 } else if guard.position == pos {
-                    match guard.direction {
-                        (-1, 0) => print!("^"),
-                        (0, 1) => print!(">"),
-                        (1, 0) => print!("v"),
-                        (0, -1) => print!("<"),
-                        _ => print!("?"),
-                    }
+                    print!("{}", guard_char(guard.direction))
                 } else {
                     print!(".")
                 }
@@ -132,12 +365,128 @@ impl GameState {
     }
 }
 
+fn guard_char(direction: Direction) -> char {
+    match direction {
+        (-1, 0) => '^',
+        (0, 1) => '>',
+        (1, 0) => 'v',
+        (0, -1) => '<',
+        _ => '?',
+    }
+}
+
 fn apply_movement(position: Position, direction: Direction) -> Position {
     (position.0 + direction.0, position.1 + direction.1)
 }
 
-fn turn_right((r_delta, c_delta): Direction) -> Direction {
-    (c_delta, r_delta * -1)
+// For every cell and heading, where the guard ends up standing when it next
+// walks into a wall, or `None` if it walks off the map first.
+struct JumpTable {
+    width: usize,
+    rules: Rules,
+    directions: usize,
+    stops: Vec<Option<Position>>,
+}
+
+impl JumpTable {
+    fn new(map: &Map) -> Self {
+        let directions = map.rules.directions();
+        let index = |(row, col): Position, d: usize| {
+            (row as usize * map.width + col as usize) * directions.len() + d
+        };
+        let mut stops = vec![None; map.width * map.height * directions.len()];
+        for (d, &(dr, dc)) in directions.iter().enumerate() {
+            // Visit cells so that the one ahead of each cell is filled in first.
+            let rows: Vec<isize> = if dr < 0 {
+                (0..map.height as isize).collect()
+            } else {
+                (0..map.height as isize).rev().collect()
+            };
+            let cols: Vec<isize> = if dc < 0 {
+                (0..map.width as isize).collect()
+            } else {
+                (0..map.width as isize).rev().collect()
+            };
+            for &row in &rows {
+                for &col in &cols {
+                    let ahead = (row + dr, col + dc);
+                    stops[index((row, col), d)] = if !map.is_in_bounds(ahead) {
+                        None
+                    } else if map.is_wall(ahead) {
+                        Some((row, col))
+                    } else {
+                        stops[index(ahead, d)]
+                    };
+                }
+            }
+        }
+        Self {
+            width: map.width,
+            rules: map.rules,
+            directions: directions.len(),
+            stops,
+        }
+    }
+
+    fn jump(&self, (row, col): Position, direction: Direction) -> Option<Position> {
+        let cell = row as usize * self.width + col as usize;
+        self.stops[cell * self.directions + self.rules.direction_index(direction)]
+    }
+
+    // Like `jump`, but as if one extra wall had been placed at `obstacle`.
+    fn jump_with_obstacle(
+        &self,
+        position: Position,
+        direction: Direction,
+        obstacle: Position,
+    ) -> Option<Position> {
+        let stop = self.jump(position, direction);
+        let steps_to = |target: Position| {
+            let (r_delta, c_delta) = (target.0 - position.0, target.1 - position.1);
+            let steps = if direction.0 != 0 {
+                r_delta / direction.0
+            } else {
+                c_delta / direction.1
+            };
+            (steps > 0 && (direction.0 * steps, direction.1 * steps) == (r_delta, c_delta))
+                .then_some(steps)
+        };
+        let Some(steps_to_obstacle) = steps_to(obstacle) else {
+            return stop;
+        };
+        let blocked_by_obstacle = match stop {
+            None => true,
+            Some(stop) => steps_to(stop).is_some_and(|steps| steps >= steps_to_obstacle),
+        };
+        if blocked_by_obstacle {
+            Some((obstacle.0 - direction.0, obstacle.1 - direction.1))
+        } else {
+            stop
+        }
+    }
+
+    // Follows the guard from wall to wall, only recording the states where it
+    // turns, until it either leaves the map or repeats a turn. A guard that
+    // finds every heading blocked without moving is trapped rather than
+    // looping, as in `GameState`.
+    fn loops_with_obstacle(&self, start: &Guard, obstacle: Position) -> bool {
+        let mut turns = HashSet::new();
+        let mut position = start.position;
+        let mut direction = start.direction;
+        let mut blocked = 0;
+        while let Some(stop) = self.jump_with_obstacle(position, direction, obstacle) {
+            blocked = if stop == position { blocked + 1 } else { 1 };
+            if blocked == self.directions {
+                return false;
+            }
+            position = stop;
+            direction = self.rules.turn(direction);
+            if !turns.insert((position, direction)) {
+                return true;
+            }
+        }
+        false
+    }
 }
 
 fn parse_input(input: impl BufRead) -> Input {
@@ -148,67 +497,101 @@ fn parse_input(input: impl BufRead) -> Input {
         .collect()
 }
 
-fn input_to_map(input: &Input) -> Map {
+fn input_to_map(input: &Input, rules: Rules) -> Map {
     let height = input.len();
     let width = input[0].len();
-    let mut guard_pos = None;
-    let mut walls = HashSet::new();
-    for row in 0..height {
-        for col in 0..width {
-            let value = input[row][col];
-            let pos = (row as isize, col as isize);
-            if value == '^' {
-                guard_pos = Some(pos);
+    let mut initial_guard = None;
+    let mut walls = BitSet::new(width * height);
+    for (row, line) in input.iter().enumerate() {
+        for (col, &value) in line.iter().enumerate() {
+            let position = (row as isize, col as isize);
+            let direction = match value {
+                '^' => Some((-1, 0)),
+                '>' => Some((0, 1)),
+                'v' => Some((1, 0)),
+                '<' => Some((0, -1)),
+                _ => None,
+            };
+            if let Some(direction) = direction {
+                initial_guard = Some(Guard {
+                    position,
+                    direction,
+                });
             }
             if value == '#' {
-                walls.insert(pos);
+                walls.insert(row * width + col);
             }
         }
     }
     Map {
-        initial_guard: Guard {
-            position: guard_pos.unwrap(),
-            direction: (-1, 0),
-        },
+        initial_guard: initial_guard.unwrap(),
         width,
         height,
         walls,
+        rules,
     }
 }
 
-fn part_1(input: &Input) -> usize {
-    let map = input_to_map(input);
-    let mut game_state = GameState::new(map);
-    while !game_state.is_game_over() {
-        game_state = game_state.next()
+fn print_path(input: &Input, rules: Rules, obstacle: Option<Position>) {
+    let mut map = input_to_map(input, rules);
+    if let Some(obstacle) = obstacle {
+        map = map.with_obstacle(obstacle);
     }
-    game_state.guard_visited_positions().len()
-}
-
-fn part_2(input: &Input) -> usize {
-    let mut possible_obstacle_positions: HashSet<Position> = HashSet::new();
-    let map = input_to_map(input);
-    let mut game_state = GameState::new(map);
-    while !game_state.is_game_over() {
-        game_state = game_state.next()
+    let game_state = GameState::new(map).run();
+    print!("{}", game_state.render_path(obstacle.as_slice()));
+    for segment in game_state.segments() {
+        println!(
+            "from {:?} heading {:?} for {} steps",
+            segment.start, segment.direction, segment.length
+        );
     }
-    for possible_obstacle_postion in game_state
-        .guard_visited_positions()
-        .into_iter()
-        .filter(|pos| *pos != game_state.map.initial_guard.position)
-    {
-        let mut possible_game_map = game_state.map.clone();
-        possible_game_map.walls.insert(possible_obstacle_postion);
-        let mut possible_game = GameState::new(possible_game_map);
-        while !possible_game.is_game_over() {
-            if possible_game.is_in_loop() {
-                possible_obstacle_positions.insert(possible_obstacle_postion);
-                break;
-            }
-            possible_game = possible_game.next()
-        }
+    println!("turning points: {:?}", game_state.turning_points());
+    match game_state.outcome().unwrap() {
+        Outcome::Looped(cycle) => println!(
+            "loops from step {} every {} steps",
+            cycle.entry_index, cycle.period
+        ),
+        Outcome::Exited => println!(
+            "leaves the map after {} steps",
+            game_state.guard_path.len() - 1
+        ),
+        Outcome::Trapped => println!("trapped after {} steps", game_state.guard_path.len() - 1),
     }
-    possible_obstacle_positions.len()
+}
+
+fn part_1(input: &Input, rules: Rules) -> usize {
+    let map = input_to_map(input, rules);
+    let game_state = GameState::new(map).run();
+    game_state.visited_count()
+}
+
+fn part_2(input: &Input, rules: Rules) -> usize {
+    let map = input_to_map(input, rules);
+    let jump_table = JumpTable::new(&map);
+    let game_state = GameState::new(map).run();
+    // An obstacle only changes the route from the first time the guard would
+    // have walked into it, so each candidate is simulated from the step
+    // before that on the original path.
+    let map = &game_state.map;
+    let mut seen = BitSet::new(map.width * map.height);
+    seen.insert(map.cell_index(map.initial_guard.position));
+    let candidates: Vec<(Guard, Position)> = game_state
+        .guard_path
+        .windows(2)
+        .filter(|step| map.is_in_bounds(step[1].position))
+        .filter(|step| seen.insert(map.cell_index(step[1].position)))
+        .map(|step| {
+            let start = Guard {
+                position: step[0].position,
+                direction: step[1].direction,
+            };
+            (start, step[1].position)
+        })
+        .collect();
+    candidates
+        .par_iter()
+        .filter(|(start, obstacle)| jump_table.loops_with_obstacle(start, *obstacle))
+        .count()
 }
 
 #[cfg(test)]
@@ -219,12 +602,181 @@ mod tests {
     #[test]
     fn test_part_1_example() {
         let input = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_1(&input), 41);
+        assert_eq!(part_1(&input, Rules::default()), 41);
     }
 
     #[test]
     fn test_part_2_example() {
         let input = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_2(&input), 6);
+        assert_eq!(part_2(&input, Rules::default()), 6);
+    }
+
+    #[test]
+    fn test_jump_table() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let map = input_to_map(&input, Rules::default());
+        let jump_table = JumpTable::new(&map);
+        assert_eq!(jump_table.jump((6, 4), (-1, 0)), Some((1, 4)));
+        assert_eq!(jump_table.jump((1, 4), (0, 1)), Some((1, 8)));
+        assert_eq!(jump_table.jump((7, 7), (1, 0)), None);
+        assert_eq!(
+            jump_table.jump_with_obstacle((6, 4), (-1, 0), (3, 4)),
+            Some((4, 4))
+        );
+        assert_eq!(
+            jump_table.jump_with_obstacle((6, 4), (-1, 0), (0, 4)),
+            Some((1, 4))
+        );
+        assert_eq!(
+            jump_table.jump_with_obstacle((7, 7), (1, 0), (9, 7)),
+            Some((8, 7))
+        );
+        assert_eq!(jump_table.jump_with_obstacle((7, 7), (1, 0), (9, 6)), None);
+
+        let map = input_to_map(&parse_input("^.#..".as_bytes()), Rules::default());
+        let jump_table = JumpTable::new(&map);
+        assert_eq!(
+            jump_table.jump_with_obstacle((0, 1), (0, 1), (0, 4)),
+            Some((0, 1))
+        );
+    }
+
+    #[test]
+    fn test_path_segments() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let game_state = GameState::new(input_to_map(&input, Rules::default())).run();
+        let segments = game_state.segments();
+        assert_eq!(
+            segments[..3],
+            [
+                Segment {
+                    start: (6, 4),
+                    direction: (-1, 0),
+                    length: 5
+                },
+                Segment {
+                    start: (1, 4),
+                    direction: (0, 1),
+                    length: 4
+                },
+                Segment {
+                    start: (1, 8),
+                    direction: (1, 0),
+                    length: 5
+                },
+            ]
+        );
+        assert_eq!(game_state.turning_points()[..2], [(1, 4), (1, 8)]);
+        assert_eq!(game_state.outcome(), Some(Outcome::Exited));
+    }
+
+    #[test]
+    fn test_loop_cycle_and_render() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let game_state =
+            GameState::new(input_to_map(&input, Rules::default()).with_obstacle((6, 3))).run();
+        assert_eq!(
+            game_state.outcome(),
+            Some(Outcome::Looped(LoopCycle {
+                entry_index: 1,
+                period: 18
+            }))
+        );
+        assert_eq!(
+            game_state.render_path(&[(6, 3)]),
+            [
+                "....#.....",
+                "....+---+#",
+                "....|...|.",
+                "..#.|...|.",
+                "....|..#|.",
+                "....|...|.",
+                ".#.O^---+.",
+                "........#.",
+                "#.........",
+                "......#...",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_start_markers() {
+        for (marker, direction) in [('^', (-1, 0)), ('>', (0, 1)), ('v', (1, 0)), ('<', (0, -1))] {
+            let input = parse_input(format!("...\n.{marker}.\n...").as_bytes());
+            let map = input_to_map(&input, Rules::default());
+            assert_eq!(
+                map.initial_guard,
+                Guard {
+                    position: (1, 1),
+                    direction
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_turn_left() {
+        let input = parse_input(".##.\n...#\n>..#\n#...".as_bytes());
+        let rules = Rules {
+            turn: Turn::Left,
+            diagonal: false,
+        };
+        let game_state = GameState::new(input_to_map(&input, rules)).run();
+        assert_eq!(game_state.turning_points(), vec![(2, 2), (1, 2)]);
+        assert_eq!(game_state.outcome(), Some(Outcome::Exited));
+    }
+
+    #[test]
+    fn test_trapped() {
+        let input = parse_input(".#.\n#^#\n.#.".as_bytes());
+        let game_state = GameState::new(input_to_map(&input, Rules::default())).run();
+        assert_eq!(game_state.outcome(), Some(Outcome::Trapped));
+
+        let rules = Rules {
+            turn: Turn::Right,
+            diagonal: true,
+        };
+        let game_state = GameState::new(input_to_map(&input, rules)).run();
+        assert_eq!(game_state.outcome(), Some(Outcome::Exited));
+        assert_eq!(game_state.guard_path[1].position, (0, 2));
+    }
+
+    #[test]
+    fn test_jumps_agree_on_trapped_guards() {
+        for text in [".#.\n#^.\n.#.", ".#..\n#^..\n.#..\n....", EXAMPLE] {
+            let map = input_to_map(&parse_input(text.as_bytes()), Rules::default());
+            let jump_table = JumpTable::new(&map);
+            for row in 0..map.height as isize {
+                for col in 0..map.width as isize {
+                    let obstacle = (row, col);
+                    if obstacle == map.initial_guard.position {
+                        continue;
+                    }
+                    let outcome = GameState::new(map.with_obstacle(obstacle)).run().outcome();
+                    assert_eq!(
+                        jump_table.loops_with_obstacle(&map.initial_guard, obstacle),
+                        matches!(outcome, Some(Outcome::Looped(_))),
+                        "{obstacle:?} in\n{text}"
+                    );
+                }
+            }
+        }
+        // The obstacle boxes the guard in, which isn't a loop.
+        let input = parse_input(".#.\n#^.\n.#.".as_bytes());
+        let map = input_to_map(&input, Rules::default());
+        let outcome = GameState::new(map.with_obstacle((1, 2))).run().outcome();
+        assert_eq!(outcome, Some(Outcome::Trapped));
+        assert!(!JumpTable::new(&map).loops_with_obstacle(&map.initial_guard, (1, 2)));
+        assert_eq!(part_2(&input, Rules::default()), 0);
+    }
+
+    #[test]
+    fn test_obstacle_off_map() {
+        let map = input_to_map(&parse_input(EXAMPLE.as_bytes()), Rules::default());
+        for obstacle in [(-1, 0), (0, -1), (10, 0), (0, 10), (10, 10)] {
+            assert_eq!(map.with_obstacle(obstacle).walls.words, map.walls.words);
+        }
     }
 }
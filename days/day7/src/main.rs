@@ -1,9 +1,60 @@
-use std::io::{stdin, BufRead};
+use std::{
+    io::{stdin, BufRead},
+    ops::ControlFlow,
+    time::Instant,
+};
 
 fn main() {
+    if std::env::args().any(|arg| arg == "--bench") {
+        bench();
+        return;
+    }
     let input = parse_input(stdin().lock());
-    println!("part 1: {}", part_1(&input));
-    println!("part 2: {}", part_2(&input));
+    let all_witnesses = std::env::args().any(|arg| arg == "--witness=all");
+    let witness = all_witnesses || std::env::args().any(|arg| arg == "--witness");
+    let unsolvable = std::env::args().any(|arg| arg == "--unsolvable");
+    let parts = [
+        (1, operators_arg("--part-1-operators=", PART_1_OPERATORS)),
+        (2, operators_arg("--part-2-operators=", PART_2_OPERATORS)),
+    ];
+    for (part, operators) in &parts {
+        if witness || unsolvable {
+            println!("part {part}:");
+        }
+        for equation in &input {
+            if all_witnesses {
+                let solutions = all_solutions(equation, operators);
+                for solution in &solutions {
+                    println!("{} = {}", equation.total, render(equation, solution));
+                }
+                if solutions.is_empty() && unsolvable {
+                    println!("{equation}");
+                }
+                continue;
+            }
+            match first_solution(equation, operators) {
+                Some(solution) if witness => println!(
+                    "{} = {} ({} solutions)",
+                    equation.total,
+                    render(equation, &solution),
+                    count_solutions(equation, operators)
+                ),
+                None if unsolvable => println!("{equation}"),
+                _ => {}
+            }
+        }
+    }
+    println!("part 1: {}", part_1(&input, &parts[0].1));
+    println!("part 2: {}", part_2(&input, &parts[1].1));
+}
+
+fn operators_arg(flag: &str, default: &[&'static dyn Operator]) -> Vec<&'static dyn Operator> {
+    match std::env::args().find_map(|arg| arg.strip_prefix(flag).map(str::to_string)) {
+        Some(list) => parse_operators(&list).unwrap_or_else(|UnknownOperator(symbol)| {
+            panic!("unknown operator {symbol:?}");
+        }),
+        None => default.to_vec(),
+    }
 }
 
 struct Equation {
@@ -11,6 +62,16 @@ struct Equation {
     operands: Vec<u64>,
 }
 
+impl std::fmt::Display for Equation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:", self.total)?;
+        for operand in &self.operands {
+            write!(f, " {operand}")?;
+        }
+        Ok(())
+    }
+}
+
 type Input = Vec<Equation>;
 
 fn parse_input(input: impl BufRead) -> Input {
@@ -21,7 +82,7 @@ fn parse_input(input: impl BufRead) -> Input {
             let (total, operands) = line.split_once(":").unwrap();
             let operands = operands
                 .split(' ')
-                .filter(|v| v.len() > 0)
+                .filter(|v| !v.is_empty())
                 .map(|v| v.parse().unwrap())
                 .collect();
             Equation {
@@ -32,55 +93,391 @@ fn parse_input(input: impl BufRead) -> Input {
         .collect()
 }
 
-fn part_1(input: &Input) -> u64 {
-    fn is_possibly_equal(desired_total: u64, total_so_far: u64, operands: &[u64]) -> bool {
-        if operands.is_empty() {
-            return total_so_far == desired_total;
-        }
-        return is_possibly_equal(desired_total, total_so_far + operands[0], &operands[1..])
-            || is_possibly_equal(desired_total, total_so_far * operands[0], &operands[1..]);
-    }
+fn part_1(input: &Input, operators: &[&'static dyn Operator]) -> u64 {
+    sum_of_solvable(input, operators, is_solvable)
+}
+
+fn part_2(input: &Input, operators: &[&'static dyn Operator]) -> u64 {
+    sum_of_solvable(input, operators, is_solvable)
+}
+
+fn sum_of_solvable(
+    input: &Input,
+    operators: &[&'static dyn Operator],
+    solver: fn(&Equation, &[&'static dyn Operator]) -> bool,
+) -> u64 {
     input
         .iter()
-        .filter(|equation| {
-            is_possibly_equal(
-                equation.total,
-                equation.operands[0],
-                &equation.operands[1..],
-            )
-        })
-        .map(|equation| equation.total as u64)
+        .filter(|equation| solver(equation, operators))
+        .map(|equation| equation.total)
         .sum()
 }
 
-fn concat(a: u64, b: u64) -> u64 {
-    a * 10u64.pow((b as f64).log10().floor() as u32 + 1) + b
+// A binary operator that can be both evaluated left to right and undone
+// from the right, given the right-hand operand. Either direction returns
+// `None` when the result doesn't fit in a `u64` (or doesn't exist), which the
+// solvers treat as a dead branch rather than letting the arithmetic wrap.
+trait Operator: Sync {
+    fn symbol(&self) -> &'static str;
+
+    fn evaluate(&self, a: u64, b: u64) -> Option<u64>;
+
+    // Which left operands `a` satisfy `a <op> b == total`.
+    fn inverse(&self, total: u64, b: u64) -> Inverse;
 }
 
-fn part_2(input: &Input) -> u64 {
-    fn is_possibly_equal(desired_total: u64, total_so_far: u64, operands: &[u64]) -> bool {
-        if operands.is_empty() {
-            return total_so_far == desired_total;
+#[derive(Debug, PartialEq, Eq)]
+enum Inverse {
+    Impossible,
+    Exactly(u64),
+    // Every `a` that can be evaluated at all gives the total.
+    Any,
+}
+
+impl From<Option<u64>> for Inverse {
+    fn from(a: Option<u64>) -> Self {
+        a.map_or(Inverse::Impossible, Inverse::Exactly)
+    }
+}
+
+struct Add;
+struct Multiply;
+struct Concat;
+struct Subtract;
+struct Xor;
+struct Power;
+
+impl Operator for Add {
+    fn symbol(&self) -> &'static str {
+        "+"
+    }
+
+    fn evaluate(&self, a: u64, b: u64) -> Option<u64> {
+        a.checked_add(b)
+    }
+
+    fn inverse(&self, total: u64, b: u64) -> Inverse {
+        total.checked_sub(b).into()
+    }
+}
+
+impl Operator for Multiply {
+    fn symbol(&self) -> &'static str {
+        "*"
+    }
+
+    fn evaluate(&self, a: u64, b: u64) -> Option<u64> {
+        a.checked_mul(b)
+    }
+
+    // Any `a` times zero is zero, so a zero total can't be undone to a
+    // single answer.
+    fn inverse(&self, total: u64, b: u64) -> Inverse {
+        match (total, b) {
+            (0, 0) => Inverse::Any,
+            (_, 0) => Inverse::Impossible,
+            _ => total.is_multiple_of(b).then(|| total / b).into(),
         }
-        return is_possibly_equal(desired_total, total_so_far + operands[0], &operands[1..])
-            || is_possibly_equal(desired_total, total_so_far * operands[0], &operands[1..])
-            || is_possibly_equal(
-                desired_total,
-                concat(total_so_far, operands[0]),
-                &operands[1..],
-            );
     }
-    input
-        .iter()
-        .filter(|equation| {
-            is_possibly_equal(
-                equation.total,
-                equation.operands[0],
-                &equation.operands[1..],
-            )
+}
+
+impl Operator for Concat {
+    fn symbol(&self) -> &'static str {
+        "||"
+    }
+
+    fn evaluate(&self, a: u64, b: u64) -> Option<u64> {
+        a.checked_mul(10u64.checked_pow(count_digits(b))?)?
+            .checked_add(b)
+    }
+
+    fn inverse(&self, total: u64, b: u64) -> Inverse {
+        // `evaluate` can't shift even a zero `a` past a 20 digit `b`.
+        let Some(shift) = 10u64.checked_pow(count_digits(b)) else {
+            return Inverse::Impossible;
+        };
+        total
+            .checked_sub(b)
+            .filter(|prefix| prefix.is_multiple_of(shift))
+            .map(|prefix| prefix / shift)
+            .into()
+    }
+}
+
+impl Operator for Subtract {
+    fn symbol(&self) -> &'static str {
+        "-"
+    }
+
+    fn evaluate(&self, a: u64, b: u64) -> Option<u64> {
+        a.checked_sub(b)
+    }
+
+    fn inverse(&self, total: u64, b: u64) -> Inverse {
+        total.checked_add(b).into()
+    }
+}
+
+impl Operator for Xor {
+    fn symbol(&self) -> &'static str {
+        "^"
+    }
+
+    fn evaluate(&self, a: u64, b: u64) -> Option<u64> {
+        Some(a ^ b)
+    }
+
+    fn inverse(&self, total: u64, b: u64) -> Inverse {
+        Inverse::Exactly(total ^ b)
+    }
+}
+
+impl Operator for Power {
+    fn symbol(&self) -> &'static str {
+        "**"
+    }
+
+    fn evaluate(&self, a: u64, b: u64) -> Option<u64> {
+        a.checked_pow(b.try_into().ok()?)
+    }
+
+    // Takes the integer `b`th root of the total, if it has one. Every `a` to
+    // the power of zero is one, so as with multiplication a total of one
+    // can't be undone to a single answer.
+    fn inverse(&self, total: u64, b: u64) -> Inverse {
+        let Ok(exponent) = u32::try_from(b) else {
+            return Inverse::Impossible;
+        };
+        if exponent == 0 {
+            return match total {
+                1 => Inverse::Any,
+                _ => Inverse::Impossible,
+            };
+        }
+        if exponent == 1 || total <= 1 {
+            return Inverse::Exactly(total);
+        }
+        let estimate = (total as f64).powf(1.0 / exponent as f64).round() as u64;
+        (estimate.saturating_sub(1)..=estimate + 1)
+            .find(|root| root.checked_pow(exponent) == Some(total))
+            .into()
+    }
+}
+
+// Every operator the solvers know about, in the order they're tried.
+const ALL_OPERATORS: &[&dyn Operator] = &[&Add, &Multiply, &Concat, &Subtract, &Xor, &Power];
+
+const PART_1_OPERATORS: &[&dyn Operator] = &[&Add, &Multiply];
+const PART_2_OPERATORS: &[&dyn Operator] = &[&Add, &Multiply, &Concat];
+
+#[derive(Debug, PartialEq, Eq)]
+struct UnknownOperator(String);
+
+// Looks up a comma separated list of operator symbols, such as `+,*,-`.
+fn parse_operators(list: &str) -> Result<Vec<&'static dyn Operator>, UnknownOperator> {
+    list.split(',')
+        .map(|symbol| {
+            ALL_OPERATORS
+                .iter()
+                .copied()
+                .find(|operator| operator.symbol() == symbol)
+                .ok_or_else(|| UnknownOperator(symbol.to_string()))
         })
-        .map(|equation| equation.total as u64)
-        .sum()
+        .collect()
+}
+
+fn count_digits(num: u64) -> u32 {
+    num.checked_ilog10().unwrap_or(0) + 1
+}
+
+// Peels operands off the end, undoing each operator in turn. Most operators
+// can only be undone for some totals (division must be exact, concatenation
+// must strip a matching suffix), which prunes most branches immediately.
+//
+// When an operator can be undone to any left operand (multiplying by zero,
+// or raising to the power of zero),
+// every prefix that evaluates without overflowing is a solution, so those are
+// enumerated forwards instead.
+//
+// `visit` is called with each satisfying sequence of operators (in left to
+// right order) and decides whether the search carries on.
+fn search(
+    equation: &Equation,
+    operators: &[&'static dyn Operator],
+    visit: &mut impl FnMut(&[&'static dyn Operator]) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    fn solve(
+        total: u64,
+        operands: &[u64],
+        operators: &[&'static dyn Operator],
+        chosen: &mut Vec<&'static dyn Operator>,
+        visit: &mut impl FnMut(&[&'static dyn Operator]) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let (&last, rest) = operands.split_last().unwrap();
+        if rest.is_empty() {
+            if total != last {
+                return ControlFlow::Continue(());
+            }
+            let in_order: Vec<_> = chosen.iter().rev().copied().collect();
+            return visit(&in_order);
+        }
+        for &operator in operators {
+            let flow = match operator.inverse(total, last) {
+                Inverse::Impossible => continue,
+                Inverse::Exactly(total) => {
+                    chosen.push(operator);
+                    solve(total, rest, operators, chosen, visit)
+                }
+                Inverse::Any => {
+                    chosen.push(operator);
+                    let (&first, rest) = rest.split_first().unwrap();
+                    evaluates(first, rest, operators, &mut Vec::new(), chosen, visit)
+                }
+            };
+            chosen.pop();
+            flow?;
+        }
+        ControlFlow::Continue(())
+    }
+    // `suffix` holds the operators already undone from the right, last first.
+    fn evaluates(
+        value: u64,
+        operands: &[u64],
+        operators: &[&'static dyn Operator],
+        prefix: &mut Vec<&'static dyn Operator>,
+        suffix: &[&'static dyn Operator],
+        visit: &mut impl FnMut(&[&'static dyn Operator]) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let Some((&next, rest)) = operands.split_first() else {
+            let in_order: Vec<_> = prefix.iter().chain(suffix.iter().rev()).copied().collect();
+            return visit(&in_order);
+        };
+        for &operator in operators {
+            if let Some(value) = operator.evaluate(value, next) {
+                prefix.push(operator);
+                let flow = evaluates(value, rest, operators, prefix, suffix, visit);
+                prefix.pop();
+                flow?;
+            }
+        }
+        ControlFlow::Continue(())
+    }
+    solve(
+        equation.total,
+        &equation.operands,
+        operators,
+        &mut Vec::new(),
+        visit,
+    )
+}
+
+fn is_solvable(equation: &Equation, operators: &[&'static dyn Operator]) -> bool {
+    first_solution(equation, operators).is_some()
+}
+
+fn first_solution(
+    equation: &Equation,
+    operators: &[&'static dyn Operator],
+) -> Option<Vec<&'static dyn Operator>> {
+    let mut found = None;
+    let _ = search(equation, operators, &mut |solution| {
+        found = Some(solution.to_vec());
+        ControlFlow::Break(())
+    });
+    found
+}
+
+fn all_solutions(
+    equation: &Equation,
+    operators: &[&'static dyn Operator],
+) -> Vec<Vec<&'static dyn Operator>> {
+    let mut found = Vec::new();
+    let _ = search(equation, operators, &mut |solution| {
+        found.push(solution.to_vec());
+        ControlFlow::Continue(())
+    });
+    found
+}
+
+fn count_solutions(equation: &Equation, operators: &[&'static dyn Operator]) -> usize {
+    let mut count = 0;
+    let _ = search(equation, operators, &mut |_| {
+        count += 1;
+        ControlFlow::Continue(())
+    });
+    count
+}
+
+// Operators are evaluated left to right, as in the puzzle, regardless of the
+// usual precedence rules.
+fn render(equation: &Equation, solution: &[&'static dyn Operator]) -> String {
+    let mut out = equation.operands[0].to_string();
+    for (operator, operand) in solution.iter().zip(&equation.operands[1..]) {
+        out.push_str(&format!(" {} {operand}", operator.symbol()));
+    }
+    out
+}
+
+// Tries every combination of operators from left to right.
+fn is_solvable_forward(equation: &Equation, operators: &[&'static dyn Operator]) -> bool {
+    fn solve(
+        desired_total: u64,
+        total_so_far: u64,
+        operands: &[u64],
+        operators: &[&'static dyn Operator],
+    ) -> bool {
+        let Some((&next, rest)) = operands.split_first() else {
+            return total_so_far == desired_total;
+        };
+        operators.iter().any(|operator| {
+            operator
+                .evaluate(total_so_far, next)
+                .is_some_and(|total_so_far| solve(desired_total, total_so_far, rest, operators))
+        })
+    }
+    solve(
+        equation.total,
+        equation.operands[0],
+        &equation.operands[1..],
+        operators,
+    )
+}
+
+// Compares both solvers on long generated equations, half of which are
+// solvable by construction and half of which are off by one.
+fn bench() {
+    let mut seed = 0x2024_0007u64;
+    let mut random = |below: u64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed % below
+    };
+    let equations: Vec<Equation> = (0..200)
+        .map(|i| {
+            let operands: Vec<u64> = (0..12).map(|_| random(9) + 1).collect();
+            let total = operands[1..].iter().fold(operands[0], |total, &operand| {
+                PART_1_OPERATORS[random(2) as usize]
+                    .evaluate(total, operand)
+                    .unwrap()
+            });
+            Equation {
+                total: total + i % 2,
+                operands,
+            }
+        })
+        .collect();
+    for (name, solver) in [
+        (
+            "forward",
+            is_solvable_forward as fn(&Equation, &[&'static dyn Operator]) -> bool,
+        ),
+        ("backward", is_solvable),
+    ] {
+        let start = Instant::now();
+        let result = sum_of_solvable(&equations, PART_2_OPERATORS, solver);
+        println!("{name}: {result} in {:?}", start.elapsed());
+    }
 }
 
 #[cfg(test)]
@@ -91,23 +488,221 @@ mod tests {
     #[test]
     fn test_part_1_example() {
         let input = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_1(&input), 3749);
+        assert_eq!(part_1(&input, PART_1_OPERATORS), 3749);
     }
 
     #[test]
     fn test_part_2_example() {
         let input = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_2(&input), 11387);
+        assert_eq!(part_2(&input, PART_2_OPERATORS), 11387);
+    }
+
+    #[test]
+    fn test_solvers_agree_on_example() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        for operators in [PART_1_OPERATORS, PART_2_OPERATORS] {
+            for equation in &input {
+                assert_eq!(
+                    is_solvable(equation, operators),
+                    is_solvable_forward(equation, operators),
+                    "{}: {:?}",
+                    equation.total,
+                    equation.operands
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_operands() {
+        let input = parse_input("3: 5 0 3\n0: 4 0\n0: 0 0 0\n7: 2 0 7\n".as_bytes());
+        assert_eq!(part_1(&input, PART_1_OPERATORS), 10);
+        assert_eq!(count_solutions(&input[2], PART_1_OPERATORS), 4);
+        let rendered: Vec<String> = all_solutions(&input[0], PART_1_OPERATORS)
+            .iter()
+            .map(|solution| render(&input[0], solution))
+            .collect();
+        assert_eq!(rendered, vec!["5 * 0 + 3"]);
+        // Every short equation over small operands, including zeros.
+        for len in 1..=4u32 {
+            for digits in 0..4u64.pow(len) {
+                let operands: Vec<u64> = (0..len).map(|i| digits / 4u64.pow(i) % 4).collect();
+                for total in 0..=30 {
+                    let equation = Equation {
+                        total,
+                        operands: operands.clone(),
+                    };
+                    for operators in [PART_1_OPERATORS, PART_2_OPERATORS] {
+                        assert_eq!(
+                            is_solvable(&equation, operators),
+                            is_solvable_forward(&equation, operators),
+                            "{equation}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_witnesses() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let equation = &input[1];
+        let solutions = all_solutions(equation, PART_1_OPERATORS);
+        let rendered: Vec<String> = solutions
+            .iter()
+            .map(|solution| render(equation, solution))
+            .collect();
+        assert_eq!(rendered, vec!["81 * 40 + 27", "81 + 40 * 27"]);
+        assert_eq!(count_solutions(equation, PART_1_OPERATORS), 2);
+        assert_eq!(
+            first_solution(&input[4], PART_2_OPERATORS).map(|s| render(&input[4], &s)),
+            Some("6 * 8 || 6 * 15".to_string())
+        );
+        assert!(first_solution(&input[2], PART_2_OPERATORS).is_none());
+    }
+
+    #[test]
+    fn test_inverse() {
+        assert_eq!(Add.inverse(10, 3), Inverse::Exactly(7));
+        assert_eq!(Add.inverse(3, 10), Inverse::Impossible);
+        assert_eq!(Multiply.inverse(12, 3), Inverse::Exactly(4));
+        assert_eq!(Multiply.inverse(13, 3), Inverse::Impossible);
+        assert_eq!(Multiply.inverse(0, 0), Inverse::Any);
+        assert_eq!(Multiply.inverse(5, 0), Inverse::Impossible);
+        assert_eq!(Concat.inverse(156, 6), Inverse::Exactly(15));
+        assert_eq!(Concat.inverse(1510, 10), Inverse::Exactly(15));
+        assert_eq!(Concat.inverse(156, 5), Inverse::Impossible);
+        assert_eq!(Concat.inverse(6, 16), Inverse::Impossible);
+        assert_eq!(Subtract.inverse(7, 3), Inverse::Exactly(10));
+        assert_eq!(Subtract.inverse(u64::MAX, 1), Inverse::Impossible);
+        assert_eq!(Xor.inverse(0b110, 0b011), Inverse::Exactly(0b101));
+        assert_eq!(Power.inverse(81, 4), Inverse::Exactly(3));
+        assert_eq!(Power.inverse(80, 4), Inverse::Impossible);
+        assert_eq!(Power.inverse(1 << 63, 63), Inverse::Exactly(2));
+        assert_eq!(Power.inverse(1, 0), Inverse::Any);
+        assert_eq!(Power.inverse(2, 0), Inverse::Impossible);
+        assert_eq!(Concat.inverse(0, u64::MAX), Inverse::Impossible);
+    }
+
+    #[test]
+    fn test_inverse_agrees_with_evaluate() {
+        let values = [
+            0,
+            1,
+            2,
+            3,
+            9,
+            10,
+            12,
+            64,
+            81,
+            1 << 32,
+            (1 << 32) + 1,
+            1 << 63,
+            10u64.pow(19) - 1,
+            10u64.pow(19),
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+        for &operator in ALL_OPERATORS {
+            let symbol = operator.symbol();
+            for &b in &values {
+                for &a in &values {
+                    if let Some(total) = operator.evaluate(a, b) {
+                        let inverse = operator.inverse(total, b);
+                        assert!(
+                            inverse == Inverse::Exactly(a) || inverse == Inverse::Any,
+                            "{a} {symbol} {b} = {total}, but undone to {inverse:?}"
+                        );
+                    }
+                }
+                for &total in &values {
+                    match operator.inverse(total, b) {
+                        Inverse::Impossible => {}
+                        Inverse::Exactly(a) => assert_eq!(
+                            operator.evaluate(a, b),
+                            Some(total),
+                            "{total} undone with {symbol} {b} to {a}"
+                        ),
+                        Inverse::Any => {
+                            for &a in &values {
+                                assert!(
+                                    operator.evaluate(a, b).is_none_or(|t| t == total),
+                                    "{a} {symbol} {b} != {total}"
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let input = parse_input("1: 7 0\n1: 2 3 0\n".as_bytes());
+        for equation in &input {
+            assert!(is_solvable(equation, ALL_OPERATORS), "{equation}");
+            assert!(is_solvable_forward(equation, ALL_OPERATORS), "{equation}");
+        }
+    }
+
+    #[test]
+    fn test_overflow_is_a_dead_branch() {
+        assert_eq!(Multiply.evaluate(u64::MAX, 2), None);
+        assert_eq!(Concat.evaluate(u64::MAX / 10, 10), None);
+        assert_eq!(Power.evaluate(2, 64), None);
+        // Wrapping would turn `MAX * 2` into `MAX - 1`.
+        let equation = Equation {
+            total: u64::MAX - 1,
+            operands: vec![u64::MAX, 2],
+        };
+        assert!(!is_solvable(&equation, ALL_OPERATORS));
+        assert!(!is_solvable_forward(&equation, ALL_OPERATORS));
+    }
+
+    #[test]
+    fn test_extra_operators() {
+        let operators = parse_operators("+,*,-,^,**").unwrap();
+        let equation = Equation {
+            total: 10,
+            operands: vec![3, 2, 5, 6],
+        };
+        assert!(!is_solvable(&equation, PART_2_OPERATORS));
+        let solutions = all_solutions(&equation, &operators);
+        for solution in &solutions {
+            let total = solution
+                .iter()
+                .zip(&equation.operands[1..])
+                .try_fold(equation.operands[0], |total, (operator, &operand)| {
+                    operator.evaluate(total, operand)
+                });
+            assert_eq!(total, Some(10), "{}", render(&equation, solution));
+        }
+        let rendered: Vec<String> = solutions
+            .iter()
+            .map(|solution| render(&equation, solution))
+            .collect();
+        assert!(rendered.contains(&"3 ** 2 - 5 + 6".to_string()));
+        assert_eq!(solutions.len(), count_solutions(&equation, &operators),);
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        let operators = parse_operators("||,**").unwrap();
+        let symbols: Vec<_> = operators.iter().map(|operator| operator.symbol()).collect();
+        assert_eq!(symbols, vec!["||", "**"]);
+        assert_eq!(
+            parse_operators("+,/").err(),
+            Some(UnknownOperator("/".to_string()))
+        );
     }
 
     #[test]
     fn test_concat() {
-        assert_eq!(concat(1, 2), 12);
-        assert_eq!(concat(9, 9), 99);
-        assert_eq!(concat(10, 10), 1010);
-        assert_eq!(concat(100, 10), 10010);
-        assert_eq!(concat(10, 100), 10100);
-        assert_eq!(concat(999, 50), 99950);
-        assert_eq!(concat(99, 50), 9950);
+        assert_eq!(Concat.evaluate(1, 2), Some(12));
+        assert_eq!(Concat.evaluate(9, 9), Some(99));
+        assert_eq!(Concat.evaluate(10, 10), Some(1010));
+        assert_eq!(Concat.evaluate(100, 10), Some(10010));
+        assert_eq!(Concat.evaluate(10, 100), Some(10100));
+        assert_eq!(Concat.evaluate(999, 50), Some(99950));
+        assert_eq!(Concat.evaluate(99, 50), Some(9950));
     }
 }
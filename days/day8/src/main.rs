@@ -1,12 +1,82 @@
+use image::{Rgb, RgbImage};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashSet},
     io::{stdin, BufRead},
+    ops::RangeInclusive,
 };
 
 fn main() {
     let input = parse_input(stdin().lock());
     println!("part 1: {}", part_1(&input));
-    println!("part 2: {}", part_2(&input));
+    let step = if std::env::args().any(|arg| arg == "--reduce") {
+        Step::Reduced
+    } else {
+        Step::Raw
+    };
+    println!("part 2: {}", part_2(&input, step));
+    let custom = resonance_args(step);
+    if let Some(resonance) = &custom {
+        println!("custom: {}", antinodes(&input, resonance).len());
+    }
+    let resonance = custom.unwrap_or_else(|| Resonance::part_2(step));
+    let by_frequency = antinodes_by_frequency(&input, &resonance);
+    if std::env::args().any(|arg| arg == "--stats") {
+        print_stats(&input, &by_frequency);
+    }
+    if std::env::args().any(|arg| arg == "--render") {
+        let antinodes = by_frequency.values().flatten().copied().collect();
+        print!("{}", render(&input, &antinodes));
+    }
+    if let Some(path) =
+        std::env::args().find_map(|arg| arg.strip_prefix("--image=").map(str::to_string))
+    {
+        render_image(&input, &by_frequency, 8).save(&path).unwrap();
+    }
+}
+
+fn print_stats(input: &AntennaMap, by_frequency: &BTreeMap<char, HashSet<Position>>) {
+    let frequencies = input.frequencies();
+    println!("frequency antennas antinodes");
+    for (frequency, antinodes) in by_frequency {
+        println!(
+            "{frequency:>9} {:>8} {:>9}",
+            frequencies[frequency].len(),
+            antinodes.len()
+        );
+    }
+    let overlaps = overlaps(by_frequency);
+    println!("overlapping antinodes: {}", overlaps.len());
+    for ((x, y), shared) in overlaps {
+        let shared: String = shared.into_iter().collect();
+        println!("  ({x}, {y}): {shared}");
+    }
+}
+
+// Builds a custom resonance from `--harmonics=K`, `--harmonics=K1..K2` or
+// `--harmonics=all`, optionally with `--with-antennas` and `--only=CHARS` or
+// `--except=CHARS`.
+fn resonance_args(step: Step) -> Option<Resonance> {
+    let arg = |prefix: &str| {
+        std::env::args().find_map(|arg| arg.strip_prefix(prefix).map(str::to_string))
+    };
+    let harmonics = match arg("--harmonics=")?.as_str() {
+        "all" => Harmonics::All,
+        range => match range.split_once("..") {
+            Some((low, high)) => Harmonics::Range(low.parse().unwrap()..=high.parse().unwrap()),
+            None => Harmonics::Exactly(range.parse().unwrap()),
+        },
+    };
+    let frequencies = match (arg("--only="), arg("--except=")) {
+        (Some(only), _) => FrequencyFilter::Only(only.chars().collect()),
+        (None, Some(except)) => FrequencyFilter::Except(except.chars().collect()),
+        (None, None) => FrequencyFilter::All,
+    };
+    Some(Resonance {
+        harmonics,
+        include_antennas: std::env::args().any(|arg| arg == "--with-antennas"),
+        frequencies,
+        step,
+    })
 }
 
 type Position = (isize, isize);
@@ -36,7 +106,7 @@ fn parse_input(input: impl BufRead) -> AntennaMap {
         .lines()
         .map(|line| line.unwrap().chars().collect())
         .collect();
-    if grid.len() == 0 || grid[0].len() == 0 {
+    if grid.is_empty() || grid[0].is_empty() {
         panic!("invalid grid")
     };
     let bounds = Bounds {
@@ -46,9 +116,8 @@ fn parse_input(input: impl BufRead) -> AntennaMap {
         right: grid[0].len() as isize - 1,
     };
     let mut nodes = Vec::new();
-    for r in 0..grid.len() {
-        for c in 0..grid[0].len() {
-            let value = grid[r][c];
+    for (r, row) in grid.iter().enumerate() {
+        for (c, &value) in row.iter().enumerate() {
             if value == '.' {
                 continue;
             }
@@ -63,48 +132,234 @@ fn parse_input(input: impl BufRead) -> AntennaMap {
     AntennaMap { bounds, nodes }
 }
 
-fn part_1(input: &AntennaMap) -> usize {
-    let mut grouped_nodes: HashMap<char, Vec<Position>> = HashMap::new();
-    for node in input.nodes.iter() {
+impl AntennaMap {
+    fn frequencies(&self) -> BTreeMap<char, Vec<Position>> {
+        let mut grouped_nodes: BTreeMap<char, Vec<Position>> = BTreeMap::new();
+        for node in self.nodes.iter() {
+            grouped_nodes
+                .entry(node.value)
+                .or_default()
+                .push(node.position);
+        }
         grouped_nodes
-            .entry(node.value)
-            .or_insert_with(Vec::new)
-            .push(node.position);
-    }
-    let next_anti_node = |a, b| anti_nodes(a, b).skip(1).take(1);
-    let anti_nodes: HashSet<_> = grouped_nodes
-        .values()
-        .flat_map(|group| {
-            in_order_pairs(group)
-                .flat_map(|(a, b)| next_anti_node(*a, *b).chain(next_anti_node(*b, *a)))
+    }
+}
+
+// Which multiples of the spacing between two antennas, counted outwards from
+// the nearer antenna, produce antinodes. Only `All` takes in the points that
+// reduced steps find in between multiples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Harmonics {
+    Exactly(usize),
+    Range(RangeInclusive<usize>),
+    All,
+}
+
+impl Harmonics {
+    fn contains(&self, harmonic: usize) -> bool {
+        match self {
+            Self::Exactly(k) => harmonic == *k,
+            Self::Range(range) => range.contains(&harmonic),
+            Self::All => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FrequencyFilter {
+    All,
+    Only(HashSet<char>),
+    Except(HashSet<char>),
+}
+
+impl FrequencyFilter {
+    fn allows(&self, frequency: char) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(frequencies) => frequencies.contains(&frequency),
+            Self::Except(frequencies) => !frequencies.contains(&frequency),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Resonance {
+    harmonics: Harmonics,
+    // Whether the antennas of a pair are antinodes themselves (harmonic 0).
+    include_antennas: bool,
+    frequencies: FrequencyFilter,
+    step: Step,
+}
+
+impl Resonance {
+    fn part_1() -> Self {
+        Resonance {
+            harmonics: Harmonics::Exactly(1),
+            include_antennas: false,
+            frequencies: FrequencyFilter::All,
+            step: Step::Raw,
+        }
+    }
+
+    fn part_2(step: Step) -> Self {
+        Resonance {
+            harmonics: Harmonics::All,
+            include_antennas: true,
+            frequencies: FrequencyFilter::All,
+            step,
+        }
+    }
+
+    fn counts(&self, harmonic: Option<usize>) -> bool {
+        match harmonic {
+            Some(0) => self.include_antennas,
+            Some(harmonic) => self.harmonics.contains(harmonic),
+            None => self.harmonics == Harmonics::All,
+        }
+    }
+}
+
+fn antinodes(input: &AntennaMap, resonance: &Resonance) -> HashSet<Position> {
+    antinodes_by_frequency(input, resonance)
+        .into_values()
+        .flatten()
+        .collect()
+}
+
+fn antinodes_by_frequency(
+    input: &AntennaMap,
+    resonance: &Resonance,
+) -> BTreeMap<char, HashSet<Position>> {
+    input
+        .frequencies()
+        .into_iter()
+        .filter(|(frequency, _)| resonance.frequencies.allows(*frequency))
+        .map(|(frequency, group)| {
+            let antinodes = in_order_pairs(&group)
+                .flat_map(|(a, b)| pair_antinodes(*a, *b, &input.bounds, resonance))
+                .collect();
+            (frequency, antinodes)
         })
-        .filter(|anit_node| is_in_bound(&input.bounds, *anit_node))
-        .collect();
+        .collect()
+}
 
-    anti_nodes.len()
+// Positions that are antinodes of more than one frequency, and which.
+fn overlaps(by_frequency: &BTreeMap<char, HashSet<Position>>) -> BTreeMap<Position, Vec<char>> {
+    let mut frequencies_at: BTreeMap<Position, Vec<char>> = BTreeMap::new();
+    for (frequency, antinodes) in by_frequency {
+        for antinode in antinodes {
+            frequencies_at
+                .entry(*antinode)
+                .or_default()
+                .push(*frequency);
+        }
+    }
+    frequencies_at.retain(|_, frequencies| frequencies.len() > 1);
+    frequencies_at
 }
 
-fn part_2(input: &AntennaMap) -> usize {
-    let mut grouped_nodes: HashMap<char, Vec<Position>> = HashMap::new();
-    for node in input.nodes.iter() {
-        grouped_nodes
-            .entry(node.value)
-            .or_insert_with(Vec::new)
-            .push(node.position);
-    }
-    let anti_nodes: HashSet<_> = grouped_nodes
-        .values()
-        .flat_map(|group| {
-            in_order_pairs(group).flat_map(|(a, b)| {
-                in_bounds_anti_nodes(*a, *b, &input.bounds).chain(in_bounds_anti_nodes(
-                    *b,
-                    *a,
-                    &input.bounds,
-                ))
-            })
+// Draws the map as in the puzzle text, with `#` marking antinodes that aren't
+// hidden under an antenna.
+fn render(input: &AntennaMap, antinodes: &HashSet<Position>) -> String {
+    let bounds = &input.bounds;
+    let width = (bounds.right - bounds.left + 1) as usize;
+    let height = (bounds.top - bounds.bottom + 1) as usize;
+    let mut grid = vec![vec!['.'; width]; height];
+    for &position in antinodes {
+        let (r, c) = grid_index(bounds, position);
+        grid[r][c] = '#';
+    }
+    for node in &input.nodes {
+        let (r, c) = grid_index(bounds, node.position);
+        grid[r][c] = node.value;
+    }
+    grid.into_iter()
+        .map(|row| row.into_iter().chain(['\n']).collect::<String>())
+        .collect()
+}
+
+// Draws each cell as a `scale` pixel square: antennas in white, antinodes in
+// their frequency's colour (or grey where frequencies overlap).
+fn render_image(
+    input: &AntennaMap,
+    by_frequency: &BTreeMap<char, HashSet<Position>>,
+    scale: u32,
+) -> RgbImage {
+    let bounds = &input.bounds;
+    let width = (bounds.right - bounds.left + 1) as u32;
+    let height = (bounds.top - bounds.bottom + 1) as u32;
+    let mut image = RgbImage::new(width * scale, height * scale);
+    let mut fill = |position: Position, colour: Rgb<u8>| {
+        let (r, c) = grid_index(bounds, position);
+        for dy in 0..scale {
+            for dx in 0..scale {
+                image.put_pixel(c as u32 * scale + dx, r as u32 * scale + dy, colour);
+            }
+        }
+    };
+    for (index, antinodes) in by_frequency.values().enumerate() {
+        for &position in antinodes {
+            fill(position, frequency_colour(index));
+        }
+    }
+    for &position in overlaps(by_frequency).keys() {
+        fill(position, Rgb([128, 128, 128]));
+    }
+    for node in &input.nodes {
+        fill(node.position, Rgb([255, 255, 255]));
+    }
+    image
+}
+
+// Spreads frequencies around the colour wheel by the golden angle so that
+// neighbouring ones are easy to tell apart.
+fn frequency_colour(index: usize) -> Rgb<u8> {
+    let hue = (index as f64 * 137.508) % 360.0;
+    let x = 1.0 - ((hue / 60.0) % 2.0 - 1.0).abs();
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    Rgb([(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8])
+}
+
+fn grid_index(bounds: &Bounds, (x, y): Position) -> (usize, usize) {
+    ((bounds.top - y) as usize, (x - bounds.left) as usize)
+}
+
+// The antinodes along the line through a pair of antennas. With `a` at step
+// 0 and `b` at step `n`, a point's harmonic is how many times `n` steps it is
+// from whichever antenna is nearer. Points that aren't a whole number of
+// times away have no harmonic.
+fn pair_antinodes<'a>(
+    a: Position,
+    b: Position,
+    bounds: &Bounds,
+    resonance: &'a Resonance,
+) -> impl Iterator<Item = Position> + 'a {
+    let n = match resonance.step {
+        Step::Raw => 1,
+        Step::Reduced => gcd(b.0 - a.0, b.1 - a.1),
+    };
+    lattice_line(a, b, bounds, resonance.step)
+        .filter(move |&(t, _)| {
+            let distance = if t <= 0 { -t } else { t - n };
+            let harmonic = (distance >= 0 && distance % n == 0).then(|| (distance / n) as usize);
+            resonance.counts(harmonic)
         })
-        .collect();
-    anti_nodes.len()
+        .map(|(_, position)| position)
+}
+
+fn part_1(input: &AntennaMap) -> usize {
+    antinodes(input, &Resonance::part_1()).len()
+}
+
+fn part_2(input: &AntennaMap, step: Step) -> usize {
+    antinodes(input, &Resonance::part_2(step)).len()
 }
 
 fn in_order_pairs<T>(items: &[T]) -> impl Iterator<Item = (&T, &T)> {
@@ -113,28 +368,62 @@ fn in_order_pairs<T>(items: &[T]) -> impl Iterator<Item = (&T, &T)> {
         .map(|(first, second)| (&items[first], &items[second]))
 }
 
-fn in_bounds_anti_nodes(
+// How far apart consecutive points on a line are. `Raw` steps by the whole
+// offset between the two antennas, as the puzzle does; `Reduced` divides it by
+// its gcd so that every grid position exactly in line is visited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Raw,
+    Reduced,
+}
+
+fn gcd(a: isize, b: isize) -> isize {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Every point `a + t * step` (for any integer `t`, in either direction) that
+// lies within `bounds`, along with its `t`, in order of increasing `t`. The
+// same point twice doesn't define a line, so yields nothing.
+fn lattice_line(
     a: Position,
     b: Position,
     bounds: &Bounds,
-) -> impl Iterator<Item = Position> {
-    let bounds = bounds.clone();
-    anti_nodes(a, b).take_while(move |node| is_in_bound(&bounds, *node))
-}
-
-fn anti_nodes(a: Position, b: Position) -> impl Iterator<Item = Position> {
-    let run = b.0 - a.0;
-    let rise = b.1 - a.1;
-    let mut current = b;
-    (0..).map(move |_| {
-        let result = current.clone();
-        current = (current.0 + run, current.1 + rise);
-        result
-    })
+    step: Step,
+) -> impl Iterator<Item = (isize, Position)> {
+    let (mut run, mut rise) = (b.0 - a.0, b.1 - a.1);
+    if step == Step::Reduced && (run, rise) != (0, 0) {
+        let divisor = gcd(run, rise);
+        (run, rise) = (run / divisor, rise / divisor);
+    }
+    let (first, last) = if (run, rise) == (0, 0) {
+        (1, 0)
+    } else {
+        let (x_first, x_last) = step_range(a.0, run, bounds.left, bounds.right);
+        let (y_first, y_last) = step_range(a.1, rise, bounds.bottom, bounds.top);
+        (x_first.max(y_first), x_last.min(y_last))
+    };
+    (first..=last).map(move |t| (t, (a.0 + t * run, a.1 + t * rise)))
 }
 
-fn is_in_bound(bounds: &Bounds, (x, y): Position) -> bool {
-    bounds.left <= x && x <= bounds.right && bounds.bottom <= y && y <= bounds.top
+// The range of `t` for which `low <= start + t * delta <= high`, which may be
+// empty.
+fn step_range(start: isize, delta: isize, low: isize, high: isize) -> (isize, isize) {
+    match delta.signum() {
+        0 if (low..=high).contains(&start) => (isize::MIN, isize::MAX),
+        0 => (1, 0),
+        1 => (
+            -(start - low).div_euclid(delta),
+            (high - start).div_euclid(delta),
+        ),
+        _ => (
+            -(high - start).div_euclid(-delta),
+            (start - low).div_euclid(-delta),
+        ),
+    }
 }
 
 #[cfg(test)]
@@ -151,7 +440,160 @@ mod tests {
     #[test]
     fn test_part_2_example() {
         let input = parse_input(EXAMPLE.as_bytes());
-        assert_eq!(part_2(&input), 34);
+        assert_eq!(part_2(&input, Step::Raw), 34);
+        assert_eq!(part_2(&input, Step::Reduced), 34);
+    }
+
+    #[test]
+    fn test_lattice_line_non_coprime_offsets() {
+        let bounds = Bounds {
+            top: 9,
+            bottom: 0,
+            left: 0,
+            right: 9,
+        };
+        let line = |a, b, step| {
+            lattice_line(a, b, &bounds, step)
+                .map(|(_, position)| position)
+                .collect::<Vec<_>>()
+        };
+        let raw = line((2, 2), (4, 6), Step::Raw);
+        assert_eq!(raw, vec![(2, 2), (4, 6)]);
+        let reduced = line((2, 2), (4, 6), Step::Reduced);
+        assert_eq!(reduced, vec![(1, 0), (2, 2), (3, 4), (4, 6), (5, 8)]);
+        let reduced = line((9, 0), (3, 6), Step::Reduced);
+        assert_eq!(
+            reduced,
+            (0..=9).rev().map(|x| (x, 9 - x)).collect::<Vec<_>>()
+        );
+        let vertical = line((4, 3), (4, 9), Step::Reduced);
+        assert_eq!(vertical, (0..=9).map(|y| (4, y)).collect::<Vec<_>>());
+        assert_eq!(
+            lattice_line((4, 3), (4, 3), &bounds, Step::Reduced).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_reduced_steps_fill_gaps() {
+        let input = parse_input("a.....\n......\n..a...\n......\n......\n......".as_bytes());
+        assert_eq!(part_2(&input, Step::Raw), 3);
+        assert_eq!(part_2(&input, Step::Reduced), 6);
+    }
+
+    #[test]
+    fn test_reduced_harmonics_are_multiples_of_the_spacing() {
+        let input = parse_input("a.....\n......\n..a...\n......\n......\n......".as_bytes());
+        let resonance = |harmonics, step| Resonance {
+            harmonics,
+            include_antennas: false,
+            frequencies: FrequencyFilter::All,
+            step,
+        };
+        for harmonics in [Harmonics::Exactly(1), Harmonics::Range(1..=3)] {
+            let raw = antinodes(&input, &resonance(harmonics.clone(), Step::Raw));
+            let reduced = antinodes(&input, &resonance(harmonics, Step::Reduced));
+            assert_eq!(raw, HashSet::from([(4, 1)]));
+            assert_eq!(reduced, raw);
+        }
+        // The point between the antennas, and those beyond them that aren't a
+        // whole number of spacings away, only count when every harmonic does.
+        let all = antinodes(&input, &resonance(Harmonics::All, Step::Reduced));
+        assert_eq!(all, HashSet::from([(1, 4), (3, 2), (4, 1), (5, 0)]));
+    }
+
+    #[test]
+    fn test_harmonics() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let resonance = |harmonics, include_antennas| Resonance {
+            harmonics,
+            include_antennas,
+            frequencies: FrequencyFilter::All,
+            step: Step::Raw,
+        };
+        let all = antinodes(&input, &resonance(Harmonics::All, true));
+        let antennas: HashSet<_> = input.nodes.iter().map(|node| node.position).collect();
+        let first = antinodes(&input, &resonance(Harmonics::Exactly(1), false));
+        let later = antinodes(&input, &resonance(Harmonics::Range(2..=usize::MAX), false));
+        let union: HashSet<_> = antennas.union(&first).chain(&later).copied().collect();
+        assert_eq!(union, all);
+        assert!(antinodes(&input, &resonance(Harmonics::Exactly(1), true)).is_superset(&antennas));
+    }
+
+    #[test]
+    fn test_frequency_filter() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let resonance = |frequencies| Resonance {
+            frequencies,
+            ..Resonance::part_1()
+        };
+        let only_a = antinodes(
+            &input,
+            &resonance(FrequencyFilter::Only(HashSet::from(['A']))),
+        );
+        let except_a = antinodes(
+            &input,
+            &resonance(FrequencyFilter::Except(HashSet::from(['A']))),
+        );
+        let zero = antinodes(
+            &input,
+            &resonance(FrequencyFilter::Only(HashSet::from(['0']))),
+        );
+        assert_eq!(except_a, zero);
+        assert_eq!(only_a.union(&zero).count(), 14);
+        assert!(antinodes(&input, &resonance(FrequencyFilter::Only(HashSet::new()))).is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let antinodes = antinodes(&input, &Resonance::part_1());
+        let expected = "\
+......#....#
+...#....0...
+....#0....#.
+..#....0....
+....0....#..
+.#....A.....
+...#........
+#......#....
+........A...
+.........A..
+..........#.
+..........#.
+";
+        assert_eq!(render(&input, &antinodes), expected);
+    }
+
+    #[test]
+    fn test_by_frequency() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let by_frequency = antinodes_by_frequency(&input, &Resonance::part_1());
+        assert_eq!(by_frequency.keys().collect::<Vec<_>>(), vec![&'0', &'A']);
+        assert_eq!(by_frequency[&'0'].len(), 10);
+        assert_eq!(by_frequency[&'A'].len(), 5);
+        let overlaps = overlaps(&by_frequency);
+        assert_eq!(overlaps, BTreeMap::from([((3, 10), vec!['0', 'A'])]));
+    }
+
+    #[test]
+    fn test_render_image() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let by_frequency = antinodes_by_frequency(&input, &Resonance::part_1());
+        let image = render_image(&input, &by_frequency, 2);
+        assert_eq!(image.dimensions(), (24, 24));
+        assert_eq!(*image.get_pixel(17, 3), Rgb([255, 255, 255]));
+        assert_eq!(*image.get_pixel(6, 2), Rgb([128, 128, 128]));
+        assert_eq!(*image.get_pixel(12, 0), frequency_colour(0));
+        assert_eq!(*image.get_pixel(0, 0), Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn test_gcd() {
+        assert_eq!(gcd(4, 6), 2);
+        assert_eq!(gcd(-4, 6), 2);
+        assert_eq!(gcd(0, -5), 5);
+        assert_eq!(gcd(7, 3), 1);
     }
 
     #[test]
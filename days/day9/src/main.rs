@@ -1,9 +1,83 @@
-use std::io::{stdin, BufRead};
+use image::{Rgb, RgbImage};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap},
+    io::{stdin, BufRead},
+    thread::sleep,
+    time::Duration,
+};
 
 fn main() {
     let input = parse_input(stdin().lock());
     println!("part 1: {}", part_1(&input));
     println!("part 2: {}", part_2(&input));
+    let disk = Disk::new(&input);
+    if std::env::args().any(|arg| arg == "--compare") {
+        compare(&disk);
+    }
+    let arg = |prefix: &str| {
+        std::env::args().find_map(|arg| arg.strip_prefix(prefix).map(str::to_string))
+    };
+    let compaction = if std::env::args().any(|arg| arg == "--blocks") {
+        compact_blocks(&disk)
+    } else {
+        compact_files(&disk, &FirstFit)
+    };
+    if std::env::args().any(|arg| arg == "--serialize") {
+        println!("{}", compaction.disk.to_disk_map());
+    }
+    let style = match arg("--render=").as_deref() {
+        Some("labels") => Some(Style::Labels),
+        Some("colour") => Some(Style::Colour),
+        Some(style) => panic!("unknown style {style:?}"),
+        None => None,
+    };
+    if let Some(style) = style {
+        let delay = arg("--animate=").map(|ms| Duration::from_millis(ms.parse().unwrap()));
+        if let Some(delay) = delay {
+            compaction.replay(&disk, |_, blocks| {
+                print!("\x1b[H\x1b[2J{}", render_blocks(blocks, style));
+                sleep(delay);
+            });
+        } else {
+            print!("{}", compaction.disk.render(style));
+        }
+    }
+    if let Some(path) = arg("--image=") {
+        animation_image(&disk, &compaction, 4).save(&path).unwrap();
+    }
+}
+
+fn compare(disk: &Disk) {
+    let strategies: [&dyn CompactionStrategy; 5] = [
+        &FirstFit,
+        &BestFit,
+        &WorstFit,
+        &AllowRight,
+        &FragmentationLimited { min_leftover: 2 },
+    ];
+    println!(
+        "{:<24} {:>20} {:>7} {:>13}",
+        "strategy", "checksum", "moves", "fragmentation"
+    );
+    let block_by_block = compact_blocks(disk);
+    let rows = [(
+        "block by block".to_string(),
+        Report::new(&block_by_block.disk, block_by_block.moves.len()),
+    )];
+    let rows = rows.into_iter().chain(strategies.iter().map(|strategy| {
+        let compaction = compact_files(disk, *strategy);
+        (
+            strategy.name(),
+            Report::new(&compaction.disk, compaction.moves.len()),
+        )
+    }));
+    for (name, report) in rows {
+        println!(
+            "{name:<24} {:>20} {:>7} {:>13.3}",
+            report.checksum, report.moves, report.fragmentation
+        );
+    }
 }
 
 type Input = Vec<u8>;
@@ -11,128 +85,614 @@ type Input = Vec<u8>;
 fn parse_input(mut input: impl BufRead) -> Input {
     let mut s = String::new();
     input.read_to_string(&mut s).unwrap();
-    s.chars().map(|c| c.to_digit(10).unwrap() as u8).collect()
+    s.trim_end()
+        .chars()
+        .map(|c| c.to_digit(10).unwrap() as u8)
+        .collect()
+}
+
+// The largest run a single digit of the disk map can describe.
+const MAX_RUN: usize = 9;
+
+// A contiguous run of blocks belonging to one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Extent {
+    id: u64,
+    start: u64,
+    len: u64,
 }
 
-fn expand_input(input: &Input) -> Vec<Option<u128>> {
-    let mut array = Vec::new();
-    let mut is_empty_block = false;
-    let mut value: u128 = 0;
-    for digit in input {
-        for _ in 0..*digit {
-            array.push(if is_empty_block { None } else { Some(value) });
+impl Extent {
+    fn end(&self) -> u64 {
+        self.start + self.len
+    }
+
+    // The sum of `position * id` over the extent's blocks.
+    fn checksum(&self) -> u128 {
+        let (start, len) = (self.start as u128, self.len as u128);
+        self.id as u128 * (start * len + len * len.saturating_sub(1) / 2)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    start: u64,
+    len: u64,
+}
+
+// Some or all of a file's blocks moving elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Move {
+    id: u64,
+    from: Span,
+    to: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    // Every block labelled with its file's id in base 36, padded to the same
+    // width, so that neighbouring files can always be told apart.
+    Labels,
+    // Every block coloured by its file's id, with free blocks left dark.
+    Colour,
+}
+
+// The disk as runs of file blocks rather than individual blocks. Anything
+// not covered by a file extent (up to `len`) is free space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Disk {
+    files: Vec<Extent>,
+    len: u64,
+}
+
+impl Disk {
+    fn new(input: &Input) -> Self {
+        let mut files = Vec::new();
+        let mut position = 0;
+        for (index, &digit) in input.iter().enumerate() {
+            let len = digit as u64;
+            if index % 2 == 0 && len > 0 {
+                files.push(Extent {
+                    id: index as u64 / 2,
+                    start: position,
+                    len,
+                });
+            }
+            position += len;
         }
-        if !is_empty_block {
-            value += 1;
+        Disk {
+            files,
+            len: position,
         }
-        is_empty_block = !is_empty_block
     }
-    array
+
+    fn checksum(&self) -> u128 {
+        self.files.iter().map(Extent::checksum).sum()
+    }
+
+    // The compact digit format, alternating file and free runs. Ids aren't
+    // part of the format, so a compacted disk reads back in with its files
+    // renumbered in order of position, and runs that don't fit in a digit are
+    // split by empty files or free runs.
+    fn to_disk_map(&self) -> String {
+        let mut map = String::new();
+        let mut position = 0;
+        let push_free = |map: &mut String, mut len: u64| {
+            while len > MAX_RUN as u64 {
+                map.push_str(&format!("{MAX_RUN}0"));
+                len -= MAX_RUN as u64;
+            }
+            map.push(char::from_digit(len as u32, 10).unwrap());
+        };
+        for file in self.sorted_files() {
+            if !map.is_empty() {
+                push_free(&mut map, file.start - position);
+            } else if file.start > 0 {
+                map.push('0');
+                push_free(&mut map, file.start);
+            }
+            map.push(char::from_digit(file.len as u32, 10).unwrap());
+            position = file.end();
+        }
+        if self.len > position {
+            if map.is_empty() {
+                map.push('0');
+            }
+            push_free(&mut map, self.len - position);
+        }
+        map
+    }
+
+    fn sorted_files(&self) -> Vec<Extent> {
+        let mut files = self.files.clone();
+        files.sort_unstable_by_key(|file| file.start);
+        files
+    }
+
+    // Which file, if any, owns each block.
+    fn blocks(&self) -> Vec<Option<u64>> {
+        let mut blocks = vec![None; self.len as usize];
+        for file in &self.files {
+            blocks[file.start as usize..file.end() as usize].fill(Some(file.id));
+        }
+        blocks
+    }
+
+    fn render(&self, style: Style) -> String {
+        render_blocks(&self.blocks(), style)
+    }
+
+    // How scattered the free space is: zero when it's all in one span, tending
+    // to one as it's split into many small ones.
+    fn fragmentation(&self) -> f64 {
+        let spans = self.free_spans();
+        let total: u64 = spans.iter().map(|span| span.len).sum();
+        let largest = spans.iter().map(|span| span.len).max().unwrap_or(0);
+        if total == 0 {
+            0.0
+        } else {
+            1.0 - largest as f64 / total as f64
+        }
+    }
+
+    // The gaps between file extents, in order.
+    fn free_spans(&self) -> Vec<Span> {
+        let mut files = self.files.clone();
+        files.sort_unstable_by_key(|file| file.start);
+        let mut spans = Vec::new();
+        let mut position = 0;
+        for file in files.iter().chain([&Extent {
+            id: 0,
+            start: self.len,
+            len: 0,
+        }]) {
+            if file.start > position {
+                spans.push(Span {
+                    start: position,
+                    len: file.start - position,
+                });
+            }
+            position = position.max(file.end());
+        }
+        spans
+    }
 }
 
 fn part_1(input: &Input) -> u128 {
-    let mut memory = expand_input(input);
+    compact_blocks(&Disk::new(input)).disk.checksum()
+}
+
+fn part_2(input: &Input) -> u128 {
+    compact_files(&Disk::new(input), &FirstFit).disk.checksum()
+}
 
-    let mut front = 0;
-    let mut back = memory.len() - 1;
-    while front < back {
-        while memory[front].is_some() {
-            front += 1;
+// The result of compacting a disk, and the moves that got it there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Compaction {
+    disk: Disk,
+    moves: Vec<Move>,
+}
+
+impl Compaction {
+    // Visits the blocks of the disk before each move, then of the final disk,
+    // numbered from zero. The moves are applied to a single block map in
+    // place, so visitors should copy out any frame they want to keep.
+    fn replay(&self, start: &Disk, mut visit: impl FnMut(usize, &[Option<u64>])) {
+        let mut blocks = start.blocks();
+        visit(0, &blocks);
+        for (index, step) in self.moves.iter().enumerate() {
+            let from = step.from.start as usize..(step.from.start + step.from.len) as usize;
+            blocks[from].fill(None);
+            let to = step.to as usize..(step.to + step.from.len) as usize;
+            blocks[to].fill(Some(step.id));
+            visit(index + 1, &blocks);
         }
-        if front >= back {
-            break;
+    }
+}
+
+fn render_blocks(blocks: &[Option<u64>], style: Style) -> String {
+    let mut out = String::new();
+    match style {
+        Style::Labels => {
+            let max_id = blocks.iter().flatten().max().copied().unwrap_or(0);
+            let width = base_36(max_id).len();
+            for block in blocks {
+                let label = match block {
+                    Some(id) => format!("{:>width$}", base_36(*id)),
+                    None => ".".repeat(width),
+                };
+                if !out.is_empty() {
+                    out.push(' ');
+                }
+                out.push_str(&label);
+            }
         }
-        while memory[back].is_none() {
-            back -= 1;
+        Style::Colour => {
+            for block in blocks {
+                match block {
+                    Some(id) => {
+                        out.push_str(&format!("\x1b[48;5;{}m \x1b[0m", 16 + palette_index(*id)))
+                    }
+                    None => out.push('.'),
+                }
+            }
         }
-        if front >= back {
+    }
+    out.push('\n');
+    out
+}
+
+fn base_36(mut num: u64) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(char::from_digit((num % 36) as u32, 36).unwrap());
+        num /= 36;
+        if num == 0 {
             break;
         }
-        memory[front] = memory[back];
-        memory[back] = None;
     }
-    memory
-        .into_iter()
-        .filter_map(|value| value)
-        .enumerate()
-        .map(|(i, value)| i as u128 * value)
-        .sum()
+    digits.into_iter().rev().collect()
 }
 
-fn part_2(input: &Input) -> u128 {
-    let mut memory = expand_input(input);
+// Consecutive ids are spread around the 6x6x6 colour cube of 256-colour
+// terminals, so neighbouring files rarely look alike.
+fn palette_index(id: u64) -> u8 {
+    (id * 97 % 216) as u8
+}
+
+fn file_colour(id: u64) -> Rgb<u8> {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let index = palette_index(id) as usize;
+    Rgb([LEVELS[index / 36], LEVELS[index / 6 % 6], LEVELS[index % 6]])
+}
+
+// The largest width or height of an animation image, in pixels.
+const MAX_IMAGE_SIZE: u32 = 4096;
 
-    let mut block_to_move_end = memory.len() - 1;
-    while block_to_move_end > 0 {
-        while memory[block_to_move_end].is_none() {
-            block_to_move_end -= 1;
+// Stacks the frames of a compaction top to bottom, one row of `scale` pixel
+// squares per frame. Past `MAX_IMAGE_SIZE`, evenly spaced frames (always
+// including the first and last) and blocks are sampled instead.
+fn animation_image(start: &Disk, compaction: &Compaction, scale: u32) -> RgbImage {
+    let limit = (MAX_IMAGE_SIZE / scale).max(1) as usize;
+    let frame_count = compaction.moves.len() + 1;
+    let rows = frame_count.min(limit);
+    let columns = (start.len as usize).min(limit);
+    // Spreads `count` samples over `0..total`, from first to last.
+    let sample =
+        |index: usize, count: usize, total: usize| index * (total - 1) / (count - 1).max(1);
+    let mut image = RgbImage::new(columns as u32 * scale, rows as u32 * scale);
+    let mut row = 0;
+    compaction.replay(start, |index, blocks| {
+        if row == rows || index != sample(row, rows, frame_count) {
+            return;
         }
-        if block_to_move_end <= 0 {
-            break;
+        for column in 0..columns {
+            let Some(id) = blocks[sample(column, columns, blocks.len())] else {
+                continue;
+            };
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    let (x, y) = (column as u32 * scale + dx, row as u32 * scale + dy);
+                    image.put_pixel(x, y, file_colour(id));
+                }
+            }
         }
-        let mut block_to_move_start = block_to_move_end;
-        while block_to_move_start > 0
-            && memory[block_to_move_start - 1] == memory[block_to_move_end]
-        {
-            block_to_move_start -= 1;
+        row += 1;
+    });
+    image
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Report {
+    checksum: u128,
+    moves: usize,
+    fragmentation: f64,
+}
+
+impl Report {
+    fn new(disk: &Disk, moves: usize) -> Self {
+        Report {
+            checksum: disk.checksum(),
+            moves,
+            fragmentation: disk.fragmentation(),
         }
-        let block_to_move_size = block_to_move_end - block_to_move_start + 1;
-        let mut front = 0;
-        loop {
-            let Some((mut empty_block_start, empty_block_end)) =
-                find_next_empty_block(&memory, front)
-            else {
+    }
+}
+
+// Moves blocks one at a time from the end of the disk into the leftmost free
+// space, splitting files as needed. Works a run at a time, so it's linear in
+// the number of runs rather than blocks.
+fn compact_blocks(disk: &Disk) -> Compaction {
+    let mut files = disk.files.clone();
+    let mut moved = Vec::new();
+    let mut moves = Vec::new();
+    for mut span in disk.free_spans() {
+        while span.len > 0 {
+            let Some(last) = files.last_mut() else {
                 break;
             };
-            if empty_block_start > block_to_move_start {
+            if last.start <= span.start {
                 break;
             }
-            let empty_block_size = empty_block_end - empty_block_start + 1;
-            if block_to_move_size > empty_block_size {
-                front = empty_block_end + 1;
-                continue;
-            }
-            for _ in 0..block_to_move_size {
-                memory[empty_block_start] = memory[block_to_move_start];
-                memory[block_to_move_start] = None;
-                empty_block_start += 1;
-                block_to_move_start += 1;
+            let len = span.len.min(last.len);
+            moved.push(Extent {
+                id: last.id,
+                start: span.start,
+                len,
+            });
+            moves.push(Move {
+                id: last.id,
+                from: Span {
+                    start: last.end() - len,
+                    len,
+                },
+                to: span.start,
+            });
+            last.len -= len;
+            if last.len == 0 {
+                files.pop();
             }
-            break;
+            span.start += len;
+            span.len -= len;
         }
-        block_to_move_end = block_to_move_start.max(1) - 1;
     }
-    memory
-        .into_iter()
-        .enumerate()
-        .filter(|(_, value)| value.is_some())
-        .map(|(i, value)| i as u128 * value.unwrap())
-        .sum()
+    files.extend(moved);
+    Compaction {
+        disk: Disk {
+            files,
+            len: disk.len,
+        },
+        moves,
+    }
+}
+
+// Decides where a whole file moves to, if anywhere, by asking `free` for the
+// span it wants.
+trait CompactionStrategy {
+    fn name(&self) -> String;
+
+    fn choose(&self, file: &Extent, free: &mut FreeSpace) -> Option<Span>;
+}
+
+// The leftmost span that fits, as in the puzzle.
+struct FirstFit;
+
+// The smallest span to the left that fits, leaving the smallest hole.
+struct BestFit;
+
+// The largest span to the left that fits, leaving the largest hole.
+struct WorstFit;
+
+// The leftmost span that fits, even if that's to the right of the file.
+struct AllowRight;
+
+// The leftmost span to the left that fits without leaving a hole smaller than
+// `min_leftover` blocks, which would be too small to be much use.
+struct FragmentationLimited {
+    min_leftover: u64,
+}
+
+impl CompactionStrategy for FirstFit {
+    fn name(&self) -> String {
+        "first fit".to_string()
+    }
+
+    fn choose(&self, file: &Extent, free: &mut FreeSpace) -> Option<Span> {
+        free.leftmost(file.start, |len| len >= file.len)
+    }
+}
+
+impl CompactionStrategy for BestFit {
+    fn name(&self) -> String {
+        "best fit".to_string()
+    }
+
+    fn choose(&self, file: &Extent, free: &mut FreeSpace) -> Option<Span> {
+        free.smallest(file.len, file.start)
+    }
+}
+
+impl CompactionStrategy for WorstFit {
+    fn name(&self) -> String {
+        "worst fit".to_string()
+    }
+
+    fn choose(&self, file: &Extent, free: &mut FreeSpace) -> Option<Span> {
+        free.largest(file.len, file.start)
+    }
 }
 
-fn find_next_empty_block(memory: &[Option<u128>], mut front: usize) -> Option<(usize, usize)> {
-    while front < memory.len() && memory[front].is_some() {
-        front += 1;
+impl CompactionStrategy for AllowRight {
+    fn name(&self) -> String {
+        "allow right".to_string()
     }
-    if front >= memory.len() {
-        return None;
+
+    fn choose(&self, file: &Extent, free: &mut FreeSpace) -> Option<Span> {
+        free.leftmost(u64::MAX, |len| len >= file.len)
+    }
+}
+
+impl CompactionStrategy for FragmentationLimited {
+    fn name(&self) -> String {
+        format!("min leftover {}", self.min_leftover)
     }
-    let mut empty_block_end = front;
-    while empty_block_end + 1 < memory.len() && memory[empty_block_end + 1].is_none() {
-        empty_block_end += 1;
+
+    fn choose(&self, file: &Extent, free: &mut FreeSpace) -> Option<Span> {
+        free.leftmost(file.start, |len| {
+            len == file.len || len >= file.len + self.min_leftover
+        })
     }
-    Some((front, empty_block_end))
 }
 
-#[allow(unused)]
-fn print_mem(memory: &[Option<u128>]) {
-    for item in memory {
-        match item {
-            None => print!("."),
-            Some(value) => print!("{value}"),
+// Free spans shorter than `MAX_RUN` blocks, bucketed by length in min-heaps of
+// their start so that the leftmost span of each length is always at hand.
+// Spans that are taken or merged are left in their heap and skipped when they
+// reach the top, as `spans` no longer agrees with them. Longer spans come in
+// too many lengths for that, so they're kept ordered both by start and by
+// length instead.
+struct FreeSpace {
+    spans: BTreeMap<u64, u64>,
+    buckets: [BinaryHeap<Reverse<(u64, u64)>>; MAX_RUN],
+    long_by_start: BTreeMap<u64, u64>,
+    long_by_len: BTreeSet<(u64, u64)>,
+}
+
+impl FreeSpace {
+    fn new(spans: &[Span]) -> Self {
+        let mut free = FreeSpace {
+            spans: BTreeMap::new(),
+            buckets: Default::default(),
+            long_by_start: BTreeMap::new(),
+            long_by_len: BTreeSet::new(),
+        };
+        for span in spans {
+            free.insert(*span);
         }
+        free
+    }
+
+    fn insert(&mut self, span: Span) {
+        self.spans.insert(span.start, span.len);
+        if span.len < MAX_RUN as u64 {
+            self.buckets[span.len as usize].push(Reverse((span.start, span.len)));
+        } else {
+            self.long_by_start.insert(span.start, span.len);
+            self.long_by_len.insert((span.len, span.start));
+        }
+    }
+
+    fn remove(&mut self, start: u64) -> Option<u64> {
+        let len = self.spans.remove(&start)?;
+        if self.long_by_start.remove(&start).is_some() {
+            self.long_by_len.remove(&(len, start));
+        }
+        Some(len)
+    }
+
+    // The leftmost span of exactly `len` blocks, which must be short.
+    fn leftmost_short(&mut self, len: u64) -> Option<Span> {
+        let bucket = &mut self.buckets[len as usize];
+        while let Some(&Reverse((start, len))) = bucket.peek() {
+            if self.spans.get(&start) == Some(&len) {
+                return Some(Span { start, len });
+            }
+            bucket.pop();
+        }
+        None
+    }
+
+    // The leftmost span starting before `before` whose length passes `fits`.
+    // Long spans are checked in order until one fits, but only up to the best
+    // short span.
+    fn leftmost(&mut self, before: u64, fits: impl Fn(u64) -> bool) -> Option<Span> {
+        let short = (1..MAX_RUN as u64)
+            .filter(|&len| fits(len))
+            .filter_map(|len| self.leftmost_short(len))
+            .filter(|span| span.start < before)
+            .min_by_key(|span| span.start);
+        let limit = short.map_or(before, |span| span.start);
+        let long = self
+            .long_by_start
+            .range(..limit)
+            .find(|&(_, &len)| fits(len))
+            .map(|(&start, &len)| Span { start, len });
+        long.or(short)
+    }
+
+    // The shortest span of at least `len` blocks starting before `before`, and
+    // the leftmost of those.
+    fn smallest(&mut self, len: u64, before: u64) -> Option<Span> {
+        (len..MAX_RUN as u64)
+            .find_map(|len| self.leftmost_short(len).filter(|span| span.start < before))
+            .or_else(|| {
+                self.long_by_len
+                    .range((len, 0)..)
+                    .find(|&&(_, start)| start < before)
+                    .map(|&(len, start)| Span { start, len })
+            })
+    }
+
+    // The longest span of at least `len` blocks starting before `before`, and
+    // the leftmost of those.
+    fn largest(&mut self, len: u64, before: u64) -> Option<Span> {
+        let long = self
+            .long_by_len
+            .range((len, 0)..)
+            .rev()
+            .find(|&&(_, start)| start < before)
+            .and_then(|&(len, _)| self.long_by_len.range((len, 0)..(len, before)).next())
+            .map(|&(len, start)| Span { start, len });
+        long.or_else(|| {
+            (len..MAX_RUN as u64)
+                .rev()
+                .find_map(|len| self.leftmost_short(len).filter(|span| span.start < before))
+        })
+    }
+
+    // Takes the first `len` blocks of `span`, which must be free.
+    fn allocate(&mut self, span: Span, len: u64) {
+        self.remove(span.start);
+        if span.len > len {
+            self.insert(Span {
+                start: span.start + len,
+                len: span.len - len,
+            });
+        }
+    }
+
+    // Frees `span`, merging it with any free neighbours.
+    fn release(&mut self, mut span: Span) {
+        if let Some((&start, &len)) = self.spans.range(..span.start).next_back() {
+            if start + len == span.start {
+                self.remove(start);
+                span = Span {
+                    start,
+                    len: len + span.len,
+                };
+            }
+        }
+        if let Some(len) = self.remove(span.start + span.len) {
+            span.len += len;
+        }
+        self.insert(span);
+    }
+}
+
+// Offers each whole file, highest id first, to `strategy` to move. Moving a
+// file is logarithmic in the number of free spans, and so are the strategies'
+// queries, apart from stepping over long spans that are out of reach or don't
+// fit.
+fn compact_files(disk: &Disk, strategy: &dyn CompactionStrategy) -> Compaction {
+    let mut free = FreeSpace::new(&disk.free_spans());
+    let mut files = disk.files.clone();
+    let mut moves = Vec::new();
+    for file in files.iter_mut().rev() {
+        assert!(file.len as usize <= MAX_RUN, "file too long");
+        let Some(span) = strategy.choose(file, &mut free) else {
+            continue;
+        };
+        free.allocate(span, file.len);
+        free.release(Span {
+            start: file.start,
+            len: file.len,
+        });
+        moves.push(Move {
+            id: file.id,
+            from: Span {
+                start: file.start,
+                len: file.len,
+            },
+            to: span.start,
+        });
+        file.start = span.start;
+    }
+    Compaction {
+        disk: Disk {
+            files,
+            len: disk.len,
+        },
+        moves,
     }
-    println!()
 }
 
 #[cfg(test)]
@@ -151,4 +711,361 @@ mod tests {
         let input = parse_input(EXAMPLE.as_bytes());
         assert_eq!(part_2(&input), 2858);
     }
+
+    #[test]
+    fn test_extent_checksum() {
+        let extent = Extent {
+            id: 7,
+            start: 3,
+            len: 4,
+        };
+        assert_eq!(extent.checksum(), 7 * (3 + 4 + 5 + 6));
+    }
+
+    #[test]
+    fn test_free_spans() {
+        let disk = Disk::new(&parse_input("12345".as_bytes()));
+        let spans: Vec<_> = disk
+            .free_spans()
+            .into_iter()
+            .map(|span| (span.start, span.len))
+            .collect();
+        assert_eq!(spans, vec![(1, 2), (6, 4)]);
+    }
+
+    fn starts(disk: &str, strategy: &dyn CompactionStrategy) -> (Vec<u64>, usize) {
+        let compaction = compact_files(&Disk::new(&parse_input(disk.as_bytes())), strategy);
+        let starts = compaction
+            .disk
+            .files
+            .iter()
+            .map(|file| file.start)
+            .collect();
+        (starts, compaction.moves.len())
+    }
+
+    #[test]
+    fn test_strategies() {
+        // 0.....1...22.33
+        assert_eq!(starts("1513212", &FirstFit), (vec![0, 5, 3, 1], 3));
+        assert_eq!(starts("1513212", &BestFit), (vec![0, 3, 1, 7], 3));
+        assert_eq!(starts("1513212", &WorstFit), (vec![0, 5, 3, 1], 3));
+        assert_eq!(
+            starts("1513212", &FragmentationLimited { min_leftover: 2 }),
+            (vec![0, 3, 10, 1], 2)
+        );
+        // 00.11122...
+        assert_eq!(starts("213023", &FirstFit), (vec![0, 3, 6], 0));
+        // File 0 follows file 2 into the space it left behind.
+        assert_eq!(starts("213023", &AllowRight), (vec![6, 3, 8], 2));
+        // 0............2..........444444444, with free spans of 12 and 10.
+        assert_eq!(starts("190319019", &BestFit).0[2], 14);
+        assert_eq!(starts("190319019", &WorstFit).0[2], 1);
+        // 0..........2............444444444, with free spans of 10 and 12.
+        assert_eq!(starts("190119039", &BestFit).0[2], 1);
+        assert_eq!(starts("190119039", &WorstFit).0[2], 12);
+        // File 5 takes the leftmost of the two 10 block spans.
+        assert_eq!(starts("89710925051", &WorstFit).0, vec![0, 10, 8, 24]);
+        // Only the long span at 21 leaves at least two blocks spare.
+        let (starts, _) = starts("99370979088", &FragmentationLimited { min_leftover: 2 });
+        assert_eq!(starts.last(), Some(&21));
+    }
+
+    #[test]
+    fn test_report() {
+        let disk = Disk::new(&parse_input("12345".as_bytes()));
+        let report = Report::new(&disk, 0);
+        assert_eq!(report.checksum, 3 + 4 + 5 + 2 * (10 + 11 + 12 + 13 + 14));
+        assert!((report.fragmentation - 1.0 / 3.0).abs() < 1e-9);
+        let compaction = compact_blocks(&disk);
+        assert_eq!(compaction.moves.len(), 2);
+        assert_eq!(compaction.disk.fragmentation(), 0.0);
+    }
+
+    #[test]
+    fn test_disk_map_round_trip() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let disk = Disk::new(&input);
+        assert_eq!(disk.to_disk_map(), EXAMPLE.trim_end());
+        for compaction in [compact_blocks(&disk), compact_files(&disk, &FirstFit)] {
+            let map = compaction.disk.to_disk_map();
+            let reread = Disk::new(&parse_input(map.as_bytes()));
+            assert_eq!(reread.len, disk.len);
+            let layout = |disk: &Disk| -> Vec<_> {
+                disk.sorted_files()
+                    .iter()
+                    .map(|file| (file.start, file.len))
+                    .collect()
+            };
+            assert_eq!(layout(&reread), layout(&compaction.disk));
+        }
+        let compacted = compact_files(&disk, &FirstFit).disk.to_disk_map();
+        assert_eq!(compacted, "20201030312134414542");
+        let long = Disk {
+            files: vec![Extent {
+                id: 0,
+                start: 12,
+                len: 3,
+            }],
+            len: 25,
+        };
+        assert_eq!(long.to_disk_map(), "09033901");
+        // The empty files padding the free run take ids 0 and 1.
+        let reread = Disk::new(&parse_input("09033901".as_bytes()));
+        assert_eq!(
+            reread.files,
+            vec![Extent {
+                id: 2,
+                start: 12,
+                len: 3
+            }]
+        );
+        assert_eq!(reread.len, long.len);
+    }
+
+    #[test]
+    fn test_render_labels() {
+        let disk = Disk::new(&parse_input("12345".as_bytes()));
+        assert_eq!(
+            disk.render(Style::Labels),
+            "0 . . 1 1 1 . . . . 2 2 2 2 2\n"
+        );
+        let compaction = compact_files(&disk, &FirstFit);
+        assert!(compaction.moves.is_empty());
+        let disk = Disk {
+            files: vec![
+                Extent {
+                    id: 35,
+                    start: 0,
+                    len: 1,
+                },
+                Extent {
+                    id: 36,
+                    start: 2,
+                    len: 2,
+                },
+            ],
+            len: 4,
+        };
+        assert_eq!(disk.render(Style::Labels), " z .. 10 10\n");
+    }
+
+    #[test]
+    fn test_frames() {
+        let input = parse_input(EXAMPLE.as_bytes());
+        let disk = Disk::new(&input);
+        for compaction in [compact_blocks(&disk), compact_files(&disk, &FirstFit)] {
+            let mut frames = Vec::new();
+            compaction.replay(&disk, |index, blocks| frames.push((index, blocks.to_vec())));
+            assert_eq!(frames.len(), compaction.moves.len() + 1);
+            assert!(frames.iter().enumerate().all(|(i, &(index, _))| i == index));
+            assert_eq!(frames[0].1, disk.blocks());
+            assert_eq!(frames.last().unwrap().1, compaction.disk.blocks());
+        }
+        let compaction = compact_blocks(&disk);
+        let mut second = String::new();
+        compaction.replay(&disk, |index, blocks| {
+            if index == 1 {
+                second = render_blocks(blocks, Style::Labels);
+            }
+        });
+        assert_eq!(
+            second.replace(' ', ""),
+            "0099.111...2...333.44.5555.6666.777.8888..\n"
+        );
+        let image = animation_image(&disk, &compaction, 2);
+        let rows = compaction.moves.len() as u32 + 1;
+        assert_eq!(image.dimensions(), (84, 2 * rows));
+    }
+
+    #[test]
+    fn test_animation_image_is_capped() {
+        let input = parse_input("11".repeat(10000).as_bytes());
+        let disk = Disk::new(&input);
+        let compaction = compact_blocks(&disk);
+        assert!(compaction.moves.len() > MAX_IMAGE_SIZE as usize);
+        assert!(disk.len > MAX_IMAGE_SIZE as u64);
+        let image = animation_image(&disk, &compaction, 1);
+        assert_eq!(image.dimensions(), (MAX_IMAGE_SIZE, MAX_IMAGE_SIZE));
+        // The last row is the compacted disk, whose files are all at the front:
+        // the second column samples block 4, where file 2 ends up.
+        let last = MAX_IMAGE_SIZE - 1;
+        assert_eq!(*image.get_pixel(1, last), file_colour(2));
+        assert_eq!(*image.get_pixel(last, last), Rgb([0, 0, 0]));
+        let scaled = animation_image(&disk, &compaction, 3);
+        assert_eq!(
+            scaled.dimensions(),
+            (MAX_IMAGE_SIZE / 3 * 3, MAX_IMAGE_SIZE / 3 * 3)
+        );
+    }
+
+    // Expands the disk one block at a time and compacts it the slow way.
+    fn expand(input: &Input) -> Vec<Option<u64>> {
+        input
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &digit)| {
+                let value = (index % 2 == 0).then_some(index as u64 / 2);
+                std::iter::repeat_n(value, digit as usize)
+            })
+            .collect()
+    }
+
+    fn block_checksum(memory: &[Option<u64>]) -> u128 {
+        memory
+            .iter()
+            .enumerate()
+            .filter_map(|(i, value)| value.map(|value| i as u128 * value as u128))
+            .sum()
+    }
+
+    fn naive_compact_files(input: &Input) -> u128 {
+        let mut memory = expand(input);
+        let files = input.len().div_ceil(2) as u64;
+        for id in (0..files).rev() {
+            let Some(start) = memory.iter().position(|&block| block == Some(id)) else {
+                continue;
+            };
+            let len = memory[start..]
+                .iter()
+                .take_while(|&&block| block == Some(id))
+                .count();
+            let fit = (0..start).find(|&i| memory[i..i + len].iter().all(Option::is_none));
+            if let Some(to) = fit {
+                memory[to..to + len].fill(Some(id));
+                memory[start..start + len].fill(None);
+            }
+        }
+        block_checksum(&memory)
+    }
+
+    // Moves whole files one block at a time, letting `pick` choose from every
+    // free span that's long enough for the file.
+    fn naive_compact_with(
+        input: &Input,
+        pick: impl Fn(Span, &[Span]) -> Option<Span>,
+    ) -> Vec<Option<u64>> {
+        let mut memory = expand(input);
+        let files = input.len().div_ceil(2) as u64;
+        for id in (0..files).rev() {
+            let Some(start) = memory.iter().position(|&block| block == Some(id)) else {
+                continue;
+            };
+            let len = memory[start..]
+                .iter()
+                .take_while(|&&block| block == Some(id))
+                .count();
+            let mut spans = Vec::new();
+            for (i, block) in memory.iter().enumerate() {
+                match (block, spans.last_mut()) {
+                    (Some(_), _) => {}
+                    (None, Some(Span { start, len })) if *start + *len == i as u64 => *len += 1,
+                    (None, _) => spans.push(Span {
+                        start: i as u64,
+                        len: 1,
+                    }),
+                }
+            }
+            spans.retain(|span| span.len >= len as u64);
+            let file = Span {
+                start: start as u64,
+                len: len as u64,
+            };
+            if let Some(span) = pick(file, &spans) {
+                let to = span.start as usize;
+                memory[start..start + len].fill(None);
+                memory[to..to + len].fill(Some(id));
+            }
+        }
+        memory
+    }
+
+    fn naive_compact_blocks(input: &Input) -> u128 {
+        let mut memory = expand(input);
+        let (mut front, mut back) = (0, memory.len());
+        loop {
+            while front < back && memory[front].is_some() {
+                front += 1;
+            }
+            while back > front && memory[back - 1].is_none() {
+                back -= 1;
+            }
+            if back <= front + 1 {
+                break;
+            }
+            memory.swap(front, back - 1);
+        }
+        block_checksum(&memory)
+    }
+
+    #[test]
+    fn test_matches_block_by_block_compaction() {
+        let mut seed = 0x2024_0009u64;
+        let mut random = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % 10) as u8
+        };
+        for _ in 0..200 {
+            let input: Input = (0..41).map(|_| random()).collect();
+            assert_eq!(part_1(&input), naive_compact_blocks(&input), "{input:?}");
+            assert_eq!(part_2(&input), naive_compact_files(&input), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_strategies_match_block_by_block_compaction() {
+        type Pick = fn(Span, &[Span]) -> Option<Span>;
+        let strategies: [(&dyn CompactionStrategy, Pick); 5] = [
+            (&FirstFit, |file, spans| {
+                spans
+                    .iter()
+                    .filter(|span| span.start < file.start)
+                    .min_by_key(|span| span.start)
+                    .copied()
+            }),
+            (&BestFit, |file, spans| {
+                spans
+                    .iter()
+                    .filter(|span| span.start < file.start)
+                    .min_by_key(|span| (span.len, span.start))
+                    .copied()
+            }),
+            (&WorstFit, |file, spans| {
+                spans
+                    .iter()
+                    .filter(|span| span.start < file.start)
+                    .max_by_key(|span| (span.len, Reverse(span.start)))
+                    .copied()
+            }),
+            (&AllowRight, |_, spans| spans.first().copied()),
+            (&FragmentationLimited { min_leftover: 2 }, |file, spans| {
+                spans
+                    .iter()
+                    .filter(|span| span.start < file.start)
+                    .find(|span| span.len == file.len || span.len >= file.len + 2)
+                    .copied()
+            }),
+        ];
+        let mut seed = 0x2024_0047u64;
+        let mut random = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % 10) as u8
+        };
+        for _ in 0..500 {
+            let input: Input = (0..41).map(|_| random()).collect();
+            let disk = Disk::new(&input);
+            for (strategy, pick) in &strategies {
+                assert_eq!(
+                    compact_files(&disk, *strategy).disk.blocks(),
+                    naive_compact_with(&input, pick),
+                    "{} {input:?}",
+                    strategy.name()
+                );
+            }
+        }
+    }
 }